@@ -1,11 +1,20 @@
+use std::{cell::RefCell, rc::Rc};
+
 use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::{from_value, to_value};
 use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::spawn_local;
 
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "tauri"])]
     async fn invoke(cmd: &str, args: JsValue) -> JsValue;
+
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "event"])]
+    async fn listen(event: &str, handler: &Closure<dyn Fn(JsValue)>) -> JsValue;
+
+    #[wasm_bindgen(js_namespace = console)]
+    fn log(s: &str);
 }
 
 #[async_trait::async_trait(?Send)]
@@ -19,30 +28,175 @@ pub trait Command: Serialize {
     }
 }
 
+/// An event pushed from the backend to the frontend
+pub trait Event: for<'a> Deserialize<'a> + 'static {
+    fn name() -> &'static str;
+
+    /// subscribes to the event, calling `handler` with each payload until
+    /// the returned subscription is dropped
+    fn subscribe(handler: impl Fn(Self) + 'static) -> Subscription {
+        let closure = Rc::new(Closure::wrap(Box::new(move |event: JsValue| {
+            // a payload we can't read, such as from a backend of another version, is
+            // skipped rather than taking the page down
+            let payload = js_sys::Reflect::get(&event, &"payload".into())
+                .map_err(|e| format!("{:?}", e))
+                .and_then(|payload| from_value(payload).map_err(|e| e.to_string()));
+            match payload {
+                Ok(payload) => handler(payload),
+                Err(e) => log(&format!("unable to read {} event: {}", Self::name(), e)),
+            }
+        }) as Box<dyn Fn(JsValue)>));
+
+        let unlisten = Rc::new(RefCell::new(Unlisten::Pending));
+
+        {
+            let closure = closure.clone();
+            let unlisten = unlisten.clone();
+            spawn_local(async move {
+                let f: js_sys::Function = listen(Self::name(), &closure).await.into();
+                let mut unlisten = unlisten.borrow_mut();
+                match *unlisten {
+                    // dropped before we finished registering
+                    Unlisten::Dropped => {
+                        f.call0(&JsValue::NULL).ok();
+                    }
+                    _ => *unlisten = Unlisten::Registered(f),
+                };
+            });
+        }
+
+        Subscription {
+            _closure: closure,
+            unlisten,
+        }
+    }
+}
+
+enum Unlisten {
+    Pending,
+    Registered(js_sys::Function),
+    Dropped,
+}
+
+/// A live event listener, which is removed on drop
+pub struct Subscription {
+    _closure: Rc<Closure<dyn Fn(JsValue)>>,
+    unlisten: Rc<RefCell<Unlisten>>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Unlisten::Registered(f) = self.unlisten.replace(Unlisten::Dropped) {
+            f.call0(&JsValue::NULL).ok();
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ListContainers {}
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub enum ListContainerResponse {
     Ok(Vec<ListContainerItem>),
     Err(String),
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct ListContainerItem {
     pub id: Option<String>,
     pub name: String,
     pub state: PodmanState,
+    /// the result of the container's healthcheck, if it has one
+    pub health: Option<ContainerHealth>,
     pub started_at: Option<i64>,
     pub exited_at: Option<i64>,
+    /// the ports published on the host
+    pub ports: Vec<PublishedPort>,
+    /// links to the container, either from the config or derived from the published ports
+    pub links: Vec<String>,
+    /// the image the container was created from
+    pub image: String,
+    /// whether the registry has a newer image than the one the container runs
+    pub update_available: bool,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PublishedPort {
+    pub host_port: u16,
+    pub container_port: u16,
+    pub protocol: String,
+}
+
+/// The state of a container as reported by the engine, in the order they are listed
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Copy)]
 pub enum PodmanState {
     Running,
+    Restarting,
+    Paused,
     Stopping,
     Stopped,
     Exited,
+    Created,
+    Configured,
+    Initialized,
+    Removing,
+    Dead,
+    Unknown,
+}
+
+impl PodmanState {
+    /// every state, in order
+    pub const ALL: [PodmanState; 12] = [
+        PodmanState::Running,
+        PodmanState::Restarting,
+        PodmanState::Paused,
+        PodmanState::Stopping,
+        PodmanState::Stopped,
+        PodmanState::Exited,
+        PodmanState::Created,
+        PodmanState::Configured,
+        PodmanState::Initialized,
+        PodmanState::Removing,
+        PodmanState::Dead,
+        PodmanState::Unknown,
+    ];
+
+    /// parses the state string used by both podman and docker
+    pub fn parse(state: &str) -> Self {
+        match state {
+            "running" => PodmanState::Running,
+            "restarting" => PodmanState::Restarting,
+            "paused" => PodmanState::Paused,
+            "stopping" => PodmanState::Stopping,
+            "stopped" => PodmanState::Stopped,
+            "exited" => PodmanState::Exited,
+            "created" => PodmanState::Created,
+            "configured" => PodmanState::Configured,
+            "initialized" => PodmanState::Initialized,
+            "removing" => PodmanState::Removing,
+            "dead" => PodmanState::Dead,
+            _ => PodmanState::Unknown,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Copy)]
+pub enum ContainerHealth {
+    Healthy,
+    Unhealthy,
+    Starting,
+}
+
+impl ContainerHealth {
+    /// parses the healthcheck status, where an empty status means there is no healthcheck
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "healthy" => Some(ContainerHealth::Healthy),
+            "unhealthy" => Some(ContainerHealth::Unhealthy),
+            "starting" => Some(ContainerHealth::Starting),
+            _ => None,
+        }
+    }
 }
 
 #[async_trait::async_trait(?Send)]
@@ -54,18 +208,351 @@ impl Command for ListContainers {
     }
 }
 
+/// An incremental change to the container list, pushed by the backend
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum ContainerEvent {
+    /// the whole list was reloaded, for example after reconnecting to the engine
+    Reset(ListContainerResponse),
+    /// a container was created or changed state
+    Updated(ListContainerItem),
+    /// a container with the given id was removed
+    Removed(String),
+}
+
+impl Event for ContainerEvent {
+    fn name() -> &'static str {
+        "containers-changed"
+    }
+}
+
+/// A lifecycle action to run against a single container
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerAction {
+    Start,
+    Stop,
+    Restart,
+    Pause,
+    Unpause,
+    Kill,
+    Remove,
+    /// remove the container and create it again with the same configuration
+    Recreate,
+    /// pull the latest image and recreate the container with it
+    Update,
+}
+
 #[derive(Deserialize, Serialize, Debug)]
-pub struct SetContainerStatus {
+pub struct RunContainerAction {
     pub id: String,
-    pub state: PodmanState,
+    pub action: ContainerAction,
+}
+
+/// The result of an action run against the engine
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum ActionResponse {
+    Ok,
+    Err(String),
 }
 
 #[async_trait::async_trait(?Send)]
-impl Command for SetContainerStatus {
+impl Command for RunContainerAction {
+    type OutputType = ActionResponse;
+
+    fn name() -> &'static str {
+        "container_action"
+    }
+}
+
+/// An action to run against every container in the pod at once
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PodAction {
+    Start,
+    Stop,
+    Restart,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RunPodAction {
+    pub action: PodAction,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for RunPodAction {
+    type OutputType = ActionResponse;
+
+    fn name() -> &'static str {
+        "pod_action"
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ListStacks {}
+
+/// A stack of containers defined in a file, that is brought up or down together
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct StackItem {
+    pub name: String,
+    pub file: String,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for ListStacks {
+    type OutputType = Vec<StackItem>;
+
+    fn name() -> &'static str {
+        "stacks_list"
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackAction {
+    Up,
+    Down,
+}
+
+/// Brings a stack up or down, reporting progress through [`StackEvent`]
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RunStackAction {
+    pub name: String,
+    pub action: StackAction,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for RunStackAction {
+    type OutputType = ActionResponse;
+
+    fn name() -> &'static str {
+        "stack_action"
+    }
+}
+
+/// Progress while bringing a stack up or down, pushed by the backend
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct StackEvent {
+    pub stack: String,
+    /// a line of output from the engine, usually about a single container
+    pub line: String,
+}
+
+impl Event for StackEvent {
+    fn name() -> &'static str {
+        "stacks"
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetDiskUsage {}
+
+/// How much space one kind of engine storage takes up
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiskUsageSummary {
+    pub count: usize,
+    /// bytes on disk
+    pub size: u64,
+    /// bytes that pruning would free up
+    pub reclaimable: u64,
+}
+
+/// The engine's equivalent of `podman system df`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DiskUsage {
+    pub images: DiskUsageSummary,
+    pub containers: DiskUsageSummary,
+    pub volumes: DiskUsageSummary,
+    /// podman keeps its build cache as untagged intermediate images
+    pub build_cache: DiskUsageSummary,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum DiskUsageResponse {
+    Ok(DiskUsage),
+    Err(String),
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for GetDiskUsage {
+    type OutputType = DiskUsageResponse;
+
+    fn name() -> &'static str {
+        "disk_usage"
+    }
+}
+
+/// What to clean up from the engine's storage
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneTarget {
+    DanglingImages,
+    StoppedContainers,
+    UnusedVolumes,
+}
+
+/// Lists what a prune would delete, without deleting anything
+#[derive(Deserialize, Serialize, Debug)]
+pub struct PreviewPrune {
+    pub target: PruneTarget,
+}
+
+/// Something that a prune would delete
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PruneCandidate {
+    pub id: String,
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum PreviewPruneResponse {
+    Ok(Vec<PruneCandidate>),
+    Err(String),
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for PreviewPrune {
+    type OutputType = PreviewPruneResponse;
+
+    fn name() -> &'static str {
+        "prune_preview"
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Prune {
+    pub target: PruneTarget,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum PruneResponse {
+    Ok { removed: usize, reclaimed: u64 },
+    Err(String),
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for Prune {
+    type OutputType = PruneResponse;
+
+    fn name() -> &'static str {
+        "prune"
+    }
+}
+
+/// Runs a command in a container to completion, without a terminal
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ExecContainer {
+    pub id: String,
+    pub command: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum ExecResponse {
+    Ok { exit_code: i64, output: String },
+    Err(String),
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for ExecContainer {
+    type OutputType = ExecResponse;
+
+    fn name() -> &'static str {
+        "container_exec"
+    }
+}
+
+/// Opens an interactive terminal in a container, running the given command
+/// or the default one from the config
+#[derive(Deserialize, Serialize, Debug)]
+pub struct OpenTerminal {
+    pub id: String,
+    pub command: Option<Vec<String>>,
+    pub rows: u16,
+    pub cols: u16,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum OpenTerminalResponse {
+    /// the session id to use for the other terminal commands
+    Ok(u32),
+    Err(String),
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for OpenTerminal {
+    type OutputType = OpenTerminalResponse;
+
+    fn name() -> &'static str {
+        "terminal_open"
+    }
+}
+
+/// Sends input to a terminal
+#[derive(Deserialize, Serialize, Debug)]
+pub struct WriteTerminal {
+    pub session: u32,
+    pub data: String,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for WriteTerminal {
+    type OutputType = ();
+
+    fn name() -> &'static str {
+        "terminal_write"
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResizeTerminal {
+    pub session: u32,
+    pub rows: u16,
+    pub cols: u16,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for ResizeTerminal {
+    type OutputType = ();
+
+    fn name() -> &'static str {
+        "terminal_resize"
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CloseTerminal {
+    pub session: u32,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for CloseTerminal {
     type OutputType = ();
 
     fn name() -> &'static str {
-        "set_container"
+        "terminal_close"
+    }
+}
+
+/// Progress while pulling the image for a container update, pushed by the backend
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PullEvent {
+    /// the container being updated
+    pub id: String,
+    pub status: String,
+}
+
+impl Event for PullEvent {
+    fn name() -> &'static str {
+        "pull"
+    }
+}
+
+/// Output from an open terminal, pushed by the backend
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum TerminalEvent {
+    Output { session: u32, data: String },
+    Exited { session: u32 },
+}
+
+impl Event for TerminalEvent {
+    fn name() -> &'static str {
+        "terminal"
     }
 }
 
@@ -134,6 +621,202 @@ pub struct ServiceHealthCheck {
     pub name: String,
     pub url: String,
     pub up: bool,
-    pub db: Option<bool>,
-    pub elasticsearch: Option<bool>,
+    /// why the check failed, when the service is down
+    pub reason: Option<String>,
+    /// how long the last attempt at the check took
+    pub latency_ms: u64,
+    /// the HTTP status of the response, for checks that make a request
+    pub status: Option<u16>,
+    /// the components reported by a spring actuator, if the service has one
+    pub components: Vec<HealthComponent>,
+    /// what the service was built from, if it reports it
+    pub build: Option<BuildInfo>,
+    /// the dependency at the root of an outage, for services that weren't checked
+    /// because something they need is down
+    pub blocked_by: Option<String>,
+}
+
+/// A healthcheck result that changed, pushed by the backend
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HealthcheckEvent {
+    /// the name of the section the service is in
+    pub section: String,
+    pub service: ServiceHealthCheck,
+}
+
+impl Event for HealthcheckEvent {
+    fn name() -> &'static str {
+        "healthcheck"
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct GetHealthHistory {
+    pub section: String,
+    pub service: String,
+    /// how far back to look, as a unix timestamp in milliseconds
+    pub since: u64,
+}
+
+/// A past result of a healthcheck
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HealthSample {
+    /// when the check ran, as a unix timestamp in milliseconds
+    pub timestamp: u64,
+    pub up: bool,
+    pub latency_ms: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum HealthHistoryResponse {
+    Ok(Vec<HealthSample>),
+    Err(String),
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for GetHealthHistory {
+    type OutputType = HealthHistoryResponse;
+
+    fn name() -> &'static str {
+        "healthcheck_history"
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct GetDependencyGraph {}
+
+#[async_trait::async_trait(?Send)]
+impl Command for GetDependencyGraph {
+    type OutputType = DependencyGraph;
+
+    fn name() -> &'static str {
+        "dependency_graph"
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    Service,
+    Tunnel,
+    Container,
+}
+
+/// Something in the dependency graph
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GraphNode {
+    pub id: String,
+    pub kind: NodeKind,
+    pub name: String,
+    /// missing for services that haven't been checked yet
+    pub up: Option<bool>,
+    pub blocked_by: Option<String>,
+}
+
+/// The services and what they depend on
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+pub struct DependencyGraph {
+    pub nodes: Vec<GraphNode>,
+    /// the ids of a dependent and its dependency
+    pub edges: Vec<(String, String)>,
+}
+
+/// Something the dashboard sends notifications about when its state changes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NotifyItem {
+    Service {
+        section: String,
+        name: String,
+    },
+    Tunnel(String),
+    /// a container, by name so that it survives being recreated
+    Container(String),
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ListMuted {}
+
+#[async_trait::async_trait(?Send)]
+impl Command for ListMuted {
+    type OutputType = Vec<NotifyItem>;
+
+    fn name() -> &'static str {
+        "notifications_muted"
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SetMuted {
+    pub item: NotifyItem,
+    pub muted: bool,
+}
+
+#[async_trait::async_trait(?Send)]
+impl Command for SetMuted {
+    type OutputType = ();
+
+    fn name() -> &'static str {
+        "notifications_mute"
+    }
+}
+
+/// The version of a running service, from its actuator
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BuildInfo {
+    pub version: Option<String>,
+    pub commit: Option<String>,
+    /// when the service was built, as an RFC 3339 timestamp
+    pub time: Option<String>,
+    /// the commit from the config, when the service runs a different one
+    pub expected_commit: Option<String>,
+    /// the version from the config, when the service runs a different one
+    pub expected_version: Option<String>,
+}
+
+/// A named health indicator of a service, such as its database
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HealthComponent {
+    pub name: String,
+    pub status: ComponentStatus,
+    /// extra information about the component, such as the database product
+    pub details: Vec<(String, String)>,
+    /// the indicators a composite component is made of
+    pub components: Vec<HealthComponent>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComponentStatus {
+    Up,
+    Down,
+    OutOfService,
+    Unknown,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct GetConfigStatus {}
+
+#[async_trait::async_trait(?Send)]
+impl Command for GetConfigStatus {
+    type OutputType = ConfigStatus;
+
+    fn name() -> &'static str {
+        "config_status"
+    }
+}
+
+/// Whether the config file loaded, pushed again every time it changes on disk
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ConfigStatus {
+    /// the path of the config file, if there is one
+    pub file: Option<String>,
+    /// what is wrong with the config, each with the file, line and column it is at
+    pub problems: Vec<String>,
+    /// whether the previous config is still in use because of the problems, rather than
+    /// the new one without what they affect
+    pub previous: bool,
+}
+
+impl Event for ConfigStatus {
+    fn name() -> &'static str {
+        "config"
+    }
 }
@@ -1,4 +1,4 @@
-use std::fs::File;
+use std::fs::{self, File};
 
 // One **exactly one** of this...
 #[path = "src/tunnel.rs"]
@@ -8,13 +8,56 @@ mod tunnel;
 #[path = "src/config.rs"]
 mod config;
 
-use schemars::schema_for;
+use schemars::{gen::SchemaGenerator, schema::Schema, schema_for};
+
+/// The definitions of the entries that are merged by name across config files
+const NAMED: &[&str] = &["Tunnel", "ServiceSection", "Service", "Container", "Stack"];
+
+fn describe(mut schema: Schema, description: &str) -> Schema {
+    if let Schema::Object(object) = &mut schema {
+        object.metadata().description = Some(description.to_string());
+    }
+    schema
+}
 
 fn main() {
     let file = File::create("./schema.json").unwrap();
 
-    let schema = schema_for!(config::Config);
+    let mut schema = schema_for!(config::Config);
+
+    // the keys for layering config files, which are handled before the config is parsed
+    let mut gen = SchemaGenerator::default();
+    schema.schema.object().properties.insert(
+        "include".to_string(),
+        describe(
+            gen.subschema_for::<Vec<std::path::PathBuf>>(),
+            "Other config files to read before this one, relative to it",
+        ),
+    );
+    schema.schema.object().properties.insert(
+        "$schema".to_string(),
+        describe(
+            gen.subschema_for::<String>(),
+            "The schema of the file, for editors",
+        ),
+    );
+    for name in NAMED {
+        if let Some(Schema::Object(definition)) = schema.definitions.get_mut(*name) {
+            definition.object().properties.insert(
+                "disabled".to_string(),
+                describe(
+                    gen.subschema_for::<bool>(),
+                    "Removes the entry with the same name from the files before this one",
+                ),
+            );
+        }
+    }
     serde_json::to_writer_pretty(file, &schema).unwrap();
 
+    // the schema of every version is kept, older ones are no longer generated
+    fs::create_dir_all("./schemas").unwrap();
+    let versioned = File::create(format!("./schemas/v{}.json", config::VERSION)).unwrap();
+    serde_json::to_writer_pretty(versioned, &schema).unwrap();
+
     tauri_build::build()
 }
@@ -0,0 +1,151 @@
+use std::{
+    path::Path,
+    process::{Output, Stdio},
+};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Certificate, ClientBuilder, Identity, RequestBuilder,
+};
+use tokio::process::Command;
+
+use crate::config::{Auth, Secret, Service, Tls};
+
+/// reads a secret from wherever the config says it lives
+async fn secret(secret: &Secret) -> Result<String, String> {
+    match secret {
+        Secret::Value(value) => Ok(value.clone()),
+        Secret::Env(name) => std::env::var(name).map_err(|e| format!("{}: {}", name, e)),
+        Secret::Keychain { service, account } => keychain(service, account).await,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn keychain_command(service: &str, account: &str) -> std::process::Command {
+    let mut command = std::process::Command::new("security");
+    command.args(["find-generic-password", "-w", "-s", service, "-a", account]);
+    command
+}
+
+#[cfg(not(target_os = "macos"))]
+fn keychain_command(service: &str, account: &str) -> std::process::Command {
+    let mut command = std::process::Command::new("secret-tool");
+    command.args(["lookup", "service", service, "account", account]);
+    command
+}
+
+async fn keychain(service: &str, account: &str) -> Result<String, String> {
+    let output = Command::from(keychain_command(service, account))
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| format!("unable to read the keychain: {}", e))?;
+    password(output, service, account)
+}
+
+/// reads a password from the keychain, for when the config is loaded
+pub fn keychain_blocking(service: &str, account: &str) -> Result<String, String> {
+    let output = keychain_command(service, account)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("unable to read the keychain: {}", e))?;
+    password(output, service, account)
+}
+
+fn password(output: Output, service: &str, account: &str) -> Result<String, String> {
+    if !output.status.success() {
+        return Err(format!(
+            "no password for {} in the keychain for {}",
+            account, service
+        ));
+    }
+    let password = String::from_utf8(output.stdout).map_err(|e| e.to_string())?;
+    Ok(password.trim_end_matches('\n').to_string())
+}
+
+/// The credentials of a check, with the secrets read
+#[derive(Clone)]
+pub enum Credentials {
+    Bearer(String),
+    Basic(String, Option<String>),
+    Header(HeaderName, HeaderValue),
+}
+
+impl Credentials {
+    pub async fn read(auth: &Auth) -> Result<Self, String> {
+        Ok(match auth {
+            Auth::Bearer { token } => Self::Bearer(secret(token).await?),
+            Auth::Basic { username, password } => Self::Basic(
+                username.clone(),
+                match password {
+                    Some(p) => Some(secret(p).await?),
+                    None => None,
+                },
+            ),
+            Auth::Header { name, value } => {
+                let mut value = header_value(&secret(value).await?)?;
+                value.set_sensitive(true);
+                Self::Header(header_name(name)?, value)
+            }
+        })
+    }
+
+    pub fn apply(&self, request: RequestBuilder) -> RequestBuilder {
+        match self {
+            Self::Bearer(token) => request.bearer_auth(token),
+            Self::Basic(username, password) => request.basic_auth(username, password.as_ref()),
+            Self::Header(name, value) => request.header(name, value),
+        }
+    }
+}
+
+/// the extra headers to send with every request of a check
+pub fn headers(service: &Service) -> Result<HeaderMap, String> {
+    service
+        .headers
+        .iter()
+        .map(|(name, value)| Ok((header_name(name)?, header_value(value)?)))
+        .collect()
+}
+
+fn header_name(name: &str) -> Result<HeaderName, String> {
+    HeaderName::from_bytes(name.as_bytes()).map_err(|e| format!("invalid header {}: {}", name, e))
+}
+
+fn header_value(value: &str) -> Result<HeaderValue, String> {
+    HeaderValue::from_str(value).map_err(|e| format!("invalid header value: {}", e))
+}
+
+/// applies the TLS settings of a check to a client, reading the files relative to the
+/// config directory
+pub fn tls(
+    mut builder: ClientBuilder,
+    tls: &Tls,
+    base: Option<&Path>,
+) -> Result<ClientBuilder, String> {
+    let read = |path: &Path| {
+        let path = match base {
+            Some(base) => base.join(path),
+            None => path.to_path_buf(),
+        };
+        std::fs::read(&path).map_err(|e| format!("unable to read {}: {}", path.display(), e))
+    };
+
+    if let Some(ca) = &tls.ca {
+        for cert in Certificate::from_pem_bundle(&read(ca)?).map_err(|e| e.to_string())? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    match (&tls.cert, &tls.key) {
+        (Some(cert), Some(key)) => {
+            let identity = Identity::from_pkcs8_pem(&read(cert)?, &read(key)?)
+                .map_err(|e| format!("invalid client certificate: {}", e))?;
+            builder = builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => return Err("a client certificate needs both a cert and a key".to_string()),
+    }
+
+    Ok(builder.danger_accept_invalid_certs(tls.insecure))
+}
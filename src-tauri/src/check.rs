@@ -0,0 +1,503 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+use commands::{HealthComponent, ServiceHealthCheck};
+use regex::Regex;
+use reqwest::{
+    header::{self, HeaderMap},
+    Client, ClientBuilder, Method, RequestBuilder, StatusCode, Url,
+};
+use serde_json::Value;
+use tauri::async_runtime::Mutex;
+use tokio::{net::TcpStream, process::Command, time::timeout};
+
+use crate::{
+    auth::{self, Credentials},
+    config::{Auth, Check, JsonAssertion, Service, Tls},
+    spring::{self, SpringHealthCheck, SpringInfo},
+};
+
+/// How long a check may take before the service counts as down, unless configured
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait between the attempts of a failing check
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The method of the standard gRPC health protocol
+const GRPC_HEALTH_CHECK: &str = "/grpc.health.v1.Health/Check";
+
+/// What a single attempt at checking a service found out
+#[derive(Default)]
+struct Outcome {
+    error: Option<String>,
+    /// the HTTP status, for the checks that make a request
+    status: Option<StatusCode>,
+    version: Option<String>,
+    components: Vec<HealthComponent>,
+}
+
+impl From<Result<(), String>> for Outcome {
+    fn from(result: Result<(), String>) -> Self {
+        Self {
+            error: result.err(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Outcome {
+    fn status(status: StatusCode, result: Result<(), String>) -> Self {
+        Self {
+            status: Some(status),
+            ..result.into()
+        }
+    }
+}
+
+/// The clients to send the requests of a check with
+#[derive(Clone)]
+struct Clients {
+    http: Client,
+    /// plaintext gRPC doesn't negotiate http2, so it needs a client that assumes it
+    grpc: Client,
+}
+
+impl Clients {
+    fn new(tls: Option<&Tls>, base: Option<&Path>) -> Result<Self, String> {
+        let build = |builder: ClientBuilder| {
+            match tls {
+                Some(tls) => auth::tls(builder, tls, base)?,
+                None => builder,
+            }
+            .build()
+            .map_err(|e| e.to_string())
+        };
+        Ok(Self {
+            http: build(Client::builder())?,
+            grpc: build(Client::builder().http2_prior_knowledge())?,
+        })
+    }
+}
+
+/// Sends the requests of a single service's check, with its headers and credentials
+struct Session {
+    clients: Clients,
+    headers: HeaderMap,
+    credentials: Option<Credentials>,
+}
+
+impl Session {
+    fn request(&self, client: &Client, method: Method, url: Url) -> RequestBuilder {
+        let request = client.request(method, url).headers(self.headers.clone());
+        match &self.credentials {
+            Some(credentials) => credentials.apply(request),
+            None => request,
+        }
+    }
+
+    fn get(&self, url: &Url) -> RequestBuilder {
+        self.request(&self.clients.http, Method::GET, url.clone())
+    }
+
+    /// a plain GET, which is up unless the server errors
+    async fn plain(&self, url: &Url, timeout: Duration) -> Outcome {
+        match self.get(url).timeout(timeout).send().await {
+            Ok(resp) => match resp.status() {
+                s if s.is_server_error() => Outcome::status(s, Err(format!("server error {}", s))),
+                s => Outcome::status(s, Ok(())),
+            },
+            Err(e) => Err(e.to_string()).into(),
+        }
+    }
+
+    async fn spring_health(&self, url: &Url, timeout: Duration) -> Outcome {
+        let resp = match self.get(url).timeout(timeout).send().await {
+            Ok(r) => r,
+            Err(e) => return Err(e.to_string()).into(),
+        };
+        let status = resp.status();
+        // spring answers 503 when a component is down, but still reports every component
+        if status != StatusCode::OK && status != StatusCode::SERVICE_UNAVAILABLE {
+            return Outcome::status(status, Err(format!("unexpected status {}", status)));
+        }
+        match resp.json::<SpringHealthCheck>().await {
+            Ok(health) => Outcome {
+                error: match health.up() {
+                    true => None,
+                    false => Some("the service reports itself as down".to_string()),
+                },
+                status: Some(status),
+                version: health.build_version(),
+                components: health.into_components(),
+            },
+            Err(e) => Outcome::status(status, Err(format!("invalid spring healthcheck: {}", e))),
+        }
+    }
+
+    async fn http(
+        &self,
+        url: &Url,
+        timeout: Duration,
+        status: &[u16],
+        body: Option<&Regex>,
+        json: &[JsonAssertion],
+    ) -> Outcome {
+        let resp = match self.get(url).timeout(timeout).send().await {
+            Ok(r) => r,
+            Err(e) => return Err(e.to_string()).into(),
+        };
+        let code = resp.status();
+        let accepted = match status {
+            [] => !code.is_server_error(),
+            status => status.contains(&code.as_u16()),
+        };
+        if !accepted {
+            return Outcome::status(code, Err(format!("unexpected status {}", code)));
+        }
+        if body.is_none() && json.is_empty() {
+            return Outcome::status(code, Ok(()));
+        }
+
+        let result = match resp.text().await {
+            Ok(text) => assert_body(&text, body, json),
+            Err(e) => Err(e.to_string()),
+        };
+        Outcome::status(code, result)
+    }
+}
+
+/// Runs the healthchecks, sharing connections between them
+pub struct Checker {
+    /// the clients for services without TLS settings of their own
+    clients: Clients,
+    /// the clients for each of the TLS settings in the config
+    tls: Mutex<HashMap<Tls, Clients>>,
+    /// the credentials for each of the auth settings in the config, so that secrets
+    /// aren't read again on every check
+    credentials: Mutex<HashMap<Auth, Credentials>>,
+    /// what the paths in the TLS settings are relative to
+    base: Option<PathBuf>,
+}
+
+impl Checker {
+    pub fn new(base: Option<PathBuf>) -> Self {
+        Self {
+            clients: Clients::new(None, None).expect("the client config is static"),
+            tls: Mutex::new(HashMap::new()),
+            credentials: Mutex::new(HashMap::new()),
+            base,
+        }
+    }
+
+    /// forgets the clients and credentials, so that the next checks pick up changes to
+    /// certificates and secrets
+    pub async fn clear(&self) {
+        self.tls.lock().await.clear();
+        self.credentials.lock().await.clear();
+    }
+
+    /// runs the check configured for a service, retrying as configured and explaining
+    /// why it failed if it did
+    pub async fn run(&self, service: Service) -> ServiceHealthCheck {
+        let timeout = service
+            .timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_TIMEOUT);
+
+        let session = self.session(&service).await;
+        let (outcome, latency) = match &session {
+            Ok(session) => {
+                let mut attempts = 0;
+                loop {
+                    let start = Instant::now();
+                    let outcome = attempt(&service, session, timeout).await;
+                    attempts += 1;
+                    if outcome.error.is_none() || attempts > service.retries {
+                        break (outcome, start.elapsed());
+                    }
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+            Err(e) => (Outcome::from(Err(e.clone())), Duration::default()),
+        };
+        let up = outcome.error.is_none();
+
+        let info_url = service.spring_info.clone().or_else(|| {
+            service
+                .spring_healthcheck
+                .as_ref()
+                .and_then(spring::info_url)
+        });
+        let info = match (info_url.filter(|_| up), &session) {
+            (Some(url), Ok(session)) => spring::info(session.get(&url), timeout).await,
+            _ => SpringInfo::default(),
+        };
+
+        ServiceHealthCheck {
+            name: service.name,
+            up,
+            url: service.url.to_string(),
+            reason: outcome.error,
+            latency_ms: latency.as_millis() as u64,
+            status: outcome.status.map(|s| s.as_u16()),
+            components: outcome.components,
+            build: info.into_build_info(
+                outcome.version,
+                service.expected_commit.as_deref(),
+                service.expected_version.as_deref(),
+            ),
+            blocked_by: None,
+        }
+    }
+
+    /// prepares the requests of a service, reading its credentials and certificates
+    async fn session(&self, service: &Service) -> Result<Session, String> {
+        let clients = match &service.tls {
+            Some(tls) => {
+                let mut cache = self.tls.lock().await;
+                match cache.get(tls) {
+                    Some(clients) => clients.clone(),
+                    None => {
+                        let clients = Clients::new(Some(tls), self.base.as_deref())?;
+                        cache.insert(tls.clone(), clients.clone());
+                        clients
+                    }
+                }
+            }
+            None => self.clients.clone(),
+        };
+        let credentials = match &service.auth {
+            Some(auth) => {
+                let cached = self.credentials.lock().await.get(auth).cloned();
+                match cached {
+                    Some(credentials) => Some(credentials),
+                    // read without the cache locked, as the keychain may prompt and the
+                    // other checks shouldn't wait on it
+                    None => {
+                        let credentials = Credentials::read(auth).await?;
+                        self.credentials
+                            .lock()
+                            .await
+                            .insert(auth.clone(), credentials.clone());
+                        Some(credentials)
+                    }
+                }
+            }
+            None => None,
+        };
+        Ok(Session {
+            clients,
+            headers: auth::headers(service)?,
+            credentials,
+        })
+    }
+}
+
+async fn attempt(service: &Service, session: &Session, timeout: Duration) -> Outcome {
+    let url = &service.url;
+    match (&service.check, &service.spring_healthcheck) {
+        (Some(Check::Tcp { address }), _) => tcp(address.as_deref(), url, timeout).await.into(),
+        (
+            Some(Check::Http {
+                url: check_url,
+                status,
+                body,
+                json,
+            }),
+            _,
+        ) => {
+            let url = check_url.as_ref().unwrap_or(url);
+            session
+                .http(url, timeout, status, body.as_ref().map(|p| &p.0), json)
+                .await
+        }
+        (Some(Check::Command { command }), _) => shell(command, timeout).await.into(),
+        (
+            Some(Check::Grpc {
+                url: check_url,
+                service,
+            }),
+            _,
+        ) => {
+            let url = check_url.as_ref().unwrap_or(url);
+            grpc(session, url, timeout, service).await.into()
+        }
+        (None, Some(health)) => session.spring_health(health, timeout).await,
+        (None, None) => session.plain(url, timeout).await,
+    }
+}
+
+fn assert_body(text: &str, body: Option<&Regex>, json: &[JsonAssertion]) -> Result<(), String> {
+    if let Some(regex) = body {
+        if !regex.is_match(text) {
+            return Err(format!("body does not match {}", regex.as_str()));
+        }
+    }
+
+    if !json.is_empty() {
+        let value: Value =
+            serde_json::from_str(text).map_err(|e| format!("body is not json: {}", e))?;
+        for assertion in json {
+            match (value.pointer(&assertion.pointer), &assertion.equals) {
+                (None, _) => return Err(format!("{} is missing", assertion.pointer)),
+                (Some(actual), Some(expected)) if actual != expected => {
+                    return Err(format!(
+                        "{} is {} instead of {}",
+                        assertion.pointer, actual, expected
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn tcp(address: Option<&str>, url: &Url, limit: Duration) -> Result<(), String> {
+    let address = match address {
+        Some(a) => a.to_string(),
+        None => format!(
+            "{}:{}",
+            url.host_str().ok_or("the url has no host")?,
+            url.port_or_known_default().ok_or("the url has no port")?
+        ),
+    };
+    match timeout(limit, TcpStream::connect(&address)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("unable to connect to {}: {}", address, e)),
+        Err(_) => Err(format!("timed out connecting to {}", address)),
+    }
+}
+
+/// the shell to run commands with
+#[cfg(windows)]
+fn shell_command() -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C");
+    command
+}
+
+#[cfg(not(windows))]
+fn shell_command() -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c");
+    command
+}
+
+/// runs a shell command, which is up when it exits successfully
+async fn shell(command: &str, limit: Duration) -> Result<(), String> {
+    let output = shell_command()
+        .arg(command)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = match timeout(limit, output).await {
+        Ok(output) => output.map_err(|e| format!("unable to run command: {}", e))?,
+        Err(_) => return Err("the command timed out".to_string()),
+    };
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(match stderr.lines().rev().find(|l| !l.trim().is_empty()) {
+        Some(line) => line.trim().to_string(),
+        None => format!("the command exited with {}", output.status),
+    })
+}
+
+/// asks a server for its health using the standard gRPC health protocol, encoding the
+/// tiny protobuf messages by hand. only a Trailers-Only response carries `grpc-status`
+/// in the headers, the trailers of a normal response can't be read, so a normal
+/// response is judged by the serving status in its body alone.
+async fn grpc(
+    session: &Session,
+    url: &Url,
+    timeout: Duration,
+    service: &str,
+) -> Result<(), String> {
+    // HealthCheckRequest { string service = 1; }
+    let mut message = vec![];
+    if !service.is_empty() {
+        message.push(0x0a);
+        push_varint(&mut message, service.len() as u64);
+        message.extend(service.as_bytes());
+    }
+    // every message is framed with a compression flag and its length
+    let mut body = vec![0];
+    body.extend((message.len() as u32).to_be_bytes());
+    body.extend(message);
+
+    // https negotiates http2 through ALPN
+    let client = match url.scheme() {
+        "http" => &session.clients.grpc,
+        _ => &session.clients.http,
+    };
+    let url = url.join(GRPC_HEALTH_CHECK).map_err(|e| e.to_string())?;
+    let resp = session
+        .request(client, Method::POST, url)
+        .header(header::CONTENT_TYPE, "application/grpc")
+        .header(header::TE, "trailers")
+        .timeout(timeout)
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // errors come back without a body, with the status in the headers
+    if let Some(status) = resp
+        .headers()
+        .get("grpc-status")
+        .and_then(|s| s.to_str().ok())
+        .filter(|s| *s != "0")
+    {
+        let message = resp
+            .headers()
+            .get("grpc-message")
+            .and_then(|m| m.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        return Err(format!("grpc status {}: {}", status, message));
+    }
+
+    let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
+    if bytes.len() < 5 {
+        return Err("empty grpc response".to_string());
+    }
+
+    // HealthCheckResponse { ServingStatus status = 1; }, which is absent when UNKNOWN
+    let status = match &bytes[5..] {
+        [0x08, rest @ ..] => read_varint(rest).unwrap_or_default(),
+        _ => 0,
+    };
+    match status {
+        1 => Ok(()),
+        2 => Err("not serving".to_string()),
+        3 => Err(format!("the server doesn't know the service {:?}", service)),
+        _ => Err("serving status unknown".to_string()),
+    }
+}
+
+fn push_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn read_varint(buf: &[u8]) -> Option<u64> {
+    let mut value = 0;
+    for (i, byte) in buf.iter().enumerate().take(10) {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
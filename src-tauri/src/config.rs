@@ -1,20 +1,51 @@
+use std::{collections::BTreeMap, net::SocketAddr, path::PathBuf};
+
+use regex::Regex;
 use reqwest::Url;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::tunnel::Tunnel;
 
+/// The version of the config this build reads, older files are migrated when they
+/// are read
+pub const VERSION: u32 = 1;
+
 /// The configuration for the developer desktop tool
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct Config {
+    /// The version of the config the file is written for, files without one are from
+    /// before there were versions and are read as version 1
+    #[serde(default = "unversioned")]
+    pub version: u32,
     /// A set of toggleable ssh tunnels
+    #[serde(default)]
     pub tunnels: Vec<Tunnel>,
     /// A set of services to healthcheck
+    #[serde(default)]
     pub services: Vec<ServiceSection>,
+    /// Per-container settings for the podman section
+    #[serde(default)]
+    pub containers: Vec<Container>,
+    /// Stacks of containers that can be brought up or down together
+    #[serde(default)]
+    pub stacks: Vec<Stack>,
+    /// A registry to check for image updates instead of the one in the image name,
+    /// such as a local mirror
+    pub registry: Option<Url>,
+    /// Where to serve the state of the dashboard in the Prometheus text format, such as
+    /// `127.0.0.1:9464`, which is then available on `/metrics`
+    pub metrics: Option<SocketAddr>,
+    /// How many days of healthcheck history to keep, defaults to 7
+    pub history_retention_days: Option<u64>,
+}
+
+fn unversioned() -> u32 {
+    1
 }
 
 /// A logical group of services to run healthchecks on
-#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct ServiceSection {
     /// The name of the section in the UI
     pub name: String,
@@ -23,7 +54,7 @@ pub struct ServiceSection {
 }
 
 /// A service to run a healthcheck on
-#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct Service {
     /// The name of the service
     pub name: String,
@@ -31,6 +62,212 @@ pub struct Service {
     pub url: Url,
     /// Optional spring healthcheck URL for more detailed information
     pub spring_healthcheck: Option<Url>,
+    /// Optional spring info URL for the build and git information, defaults to the
+    /// `info` endpoint next to the spring healthcheck
+    pub spring_info: Option<Url>,
+    /// The git commit the service is expected to run, or a prefix of it, which is
+    /// flagged in the UI when the service runs something else
+    pub expected_commit: Option<String>,
+    /// The version the service is expected to run, compared with the version of its build
+    /// info, which is flagged in the UI when the service runs another one
+    pub expected_version: Option<String>,
     /// Optional command to start the service
     pub command: Option<String>,
+    /// How to check the service, instead of a GET on the url or the spring healthcheck
+    pub check: Option<Check>,
+    /// How long the check may take in milliseconds, defaults to 5 seconds
+    pub timeout_ms: Option<u64>,
+    /// How many times to retry a failing check before reporting the service as down
+    #[serde(default)]
+    pub retries: u32,
+    /// How often to check the service in seconds, defaults to 10 seconds
+    pub interval_secs: Option<u64>,
+    /// Extra headers to send with every request of the check
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// How to authenticate the requests of the check
+    pub auth: Option<Auth>,
+    /// TLS settings for the requests of the check
+    pub tls: Option<Tls>,
+    /// What the service needs to work. The service isn't checked while any of them is
+    /// down, and is reported as blocked by it instead.
+    #[serde(default)]
+    pub depends_on: Vec<Dependency>,
+}
+
+/// Something a service depends on, by name
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Dependency {
+    /// An ssh tunnel, which has to be connected
+    Tunnel(String),
+    /// A container in the podman section, which has to be running. It needs an entry
+    /// under `containers`, if only with its name.
+    Container(String),
+    /// Another service, which has to be up
+    Service(String),
+}
+
+/// Credentials to send with the requests of a check
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, JsonSchema)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Auth {
+    /// Sends an `Authorization: Bearer` token
+    Bearer {
+        /// The token
+        token: Secret,
+    },
+    /// Sends `Authorization: Basic` credentials
+    Basic {
+        /// The user name
+        username: String,
+        /// The password, if there is one
+        password: Option<Secret>,
+    },
+    /// Sends a secret in a header of its own, such as an API key
+    Header {
+        /// The name of the header
+        name: String,
+        /// The value of the header
+        value: Secret,
+    },
+}
+
+/// A secret that is better kept out of the config file
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Secret {
+    /// The secret itself
+    Value(String),
+    /// The name of an environment variable holding the secret
+    Env(String),
+    /// A generic password in the OS keychain, read with `security` on macOS and
+    /// `secret-tool` elsewhere
+    Keychain {
+        /// The service the password is stored under
+        service: String,
+        /// The account the password is stored under
+        account: String,
+    },
+}
+
+/// TLS settings for the requests of a check. Paths are relative to the config file.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Default, JsonSchema)]
+pub struct Tls {
+    /// A PEM bundle of extra certificate authorities to trust, such as an internal CA
+    pub ca: Option<PathBuf>,
+    /// A PEM client certificate to present to the server
+    pub cert: Option<PathBuf>,
+    /// The PKCS #8 PEM private key of the client certificate
+    pub key: Option<PathBuf>,
+    /// Accepts any server certificate, which is only ever a good idea for local testing
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+/// A way to decide whether a service is up
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Check {
+    /// Up when a TCP connection can be opened
+    Tcp {
+        /// The `host:port` to connect to, defaults to the host and port of the url
+        address: Option<String>,
+    },
+    /// Up when the response passes all the assertions
+    Http {
+        /// The url to request, defaults to the url of the service
+        url: Option<Url>,
+        /// The accepted status codes, defaults to anything but a server error
+        #[serde(default)]
+        status: Vec<u16>,
+        /// A regular expression the body has to match
+        #[schemars(with = "Option<String>")]
+        body: Option<Pattern>,
+        /// Values the body has to contain when parsed as JSON
+        #[serde(default)]
+        json: Vec<JsonAssertion>,
+    },
+    /// Up when the shell command exits successfully
+    Command {
+        /// The command, run with `sh -c`, or `cmd /C` on Windows
+        command: String,
+    },
+    /// Up when the server reports serving through the standard gRPC health protocol.
+    /// Only an error the server answers with right away is read, as the status of a
+    /// complete response comes in trailers, which can't be read.
+    Grpc {
+        /// The url of the server, defaults to the url of the service
+        url: Option<Url>,
+        /// The service to ask about, defaults to the server as a whole
+        #[serde(default)]
+        service: String,
+    },
+}
+
+/// A regular expression, compiled when the config is read
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub struct Pattern(pub Regex);
+
+impl TryFrom<String> for Pattern {
+    type Error = regex::Error;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        Regex::new(&pattern).map(Self)
+    }
+}
+
+impl From<Pattern> for String {
+    fn from(pattern: Pattern) -> Self {
+        pattern.0.as_str().to_string()
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+/// An assertion on a value in a JSON response
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct JsonAssertion {
+    /// A JSON pointer to the value, such as `/status`, rather than a JSONPath expression
+    pub pointer: String,
+    /// The value it has to equal, or just that it exists when missing
+    pub equals: Option<serde_json::Value>,
+}
+
+/// Settings for a container in the podman section, matched by name
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct Container {
+    /// The name of the container
+    pub name: String,
+    /// Links to show on the card, replacing the ones derived from published ports
+    #[serde(default)]
+    pub links: Vec<Url>,
+    /// The command to run when opening a terminal in the container, defaults to `sh`
+    pub exec_command: Option<Vec<String>>,
+}
+
+/// A set of containers defined in a single file
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct Stack {
+    /// The name of the stack in the UI
+    pub name: String,
+    /// The kubernetes yaml or compose file, relative to the config file
+    pub file: PathBuf,
+    /// The kind of file, guessed from the file name when missing
+    pub kind: Option<StackKind>,
+}
+
+/// The format of a stack file
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StackKind {
+    /// Played with `podman kube play`
+    Kube,
+    /// Run with `podman compose`
+    Compose,
 }
@@ -0,0 +1,202 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use commands::{Event, ExecResponse, TerminalEvent};
+use futures::StreamExt;
+use podman_api::{conn::TtyChunk, opts::ExecCreateOpts};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use tauri::{
+    async_runtime::{block_on, Mutex},
+    AppHandle, Manager,
+};
+
+use crate::podman::podman;
+
+/// The command used for a shell when the config doesn't specify one
+const DEFAULT_SHELL: &str = "sh";
+
+/// runs a command in a container to completion, collecting its output
+pub async fn exec(id: &str, command: Vec<String>) -> ExecResponse {
+    let podman = podman();
+    let exec = match podman
+        .containers()
+        .get(id)
+        .create_exec(
+            &ExecCreateOpts::builder()
+                .command(command)
+                .attach_stdout(true)
+                .attach_stderr(true)
+                .build(),
+        )
+        .await
+    {
+        Ok(exec) => exec,
+        Err(e) => return ExecResponse::Err(e.to_string()),
+    };
+
+    let opts = Default::default();
+    let mut stream = exec.start(&opts);
+    let mut output = vec![];
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(TtyChunk::StdOut(bytes) | TtyChunk::StdErr(bytes)) => output.extend(bytes),
+            Ok(TtyChunk::StdIn(_)) => {}
+            Err(e) => return ExecResponse::Err(e.to_string()),
+        }
+    }
+
+    match exec.inspect().await {
+        Ok(info) => ExecResponse::Ok {
+            exit_code: info["ExitCode"].as_i64().unwrap_or_default(),
+            output: String::from_utf8_lossy(&output).to_string(),
+        },
+        Err(e) => ExecResponse::Err(e.to_string()),
+    }
+}
+
+/// An interactive `podman exec` running in a local pty
+struct Terminal {
+    master: Box<dyn MasterPty + Send>,
+    /// writes block, so they happen outside of the lock on the sessions
+    writer: Arc<std::sync::Mutex<Box<dyn Write + Send>>>,
+    child: Box<dyn Child + Send + Sync>,
+}
+
+/// The open terminals, keyed by session id
+#[derive(Default)]
+pub struct TerminalState {
+    next: AtomicU32,
+    sessions: Mutex<HashMap<u32, Terminal>>,
+}
+
+impl TerminalState {
+    /// opens a terminal in the container, streaming its output to the frontend
+    pub async fn open(
+        &self,
+        app: AppHandle,
+        id: &str,
+        command: Vec<String>,
+        rows: u16,
+        cols: u16,
+    ) -> Result<u32, String> {
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows,
+                cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut cmd = CommandBuilder::new("podman");
+        cmd.args(["exec", "-it", id]);
+        if command.is_empty() {
+            cmd.arg(DEFAULT_SHELL);
+        } else {
+            cmd.args(command);
+        }
+
+        let child = pair.slave.spawn_command(cmd).map_err(|e| e.to_string())?;
+        let mut reader = pair.master.try_clone_reader().map_err(|e| e.to_string())?;
+        let writer = pair.master.take_writer().map_err(|e| e.to_string())?;
+
+        let session = self.next.fetch_add(1, Ordering::Relaxed);
+        self.sessions.lock().await.insert(
+            session,
+            Terminal {
+                master: pair.master,
+                writer: Arc::new(std::sync::Mutex::new(writer)),
+                child,
+            },
+        );
+
+        // reads on the pty block, so they get a thread of their own
+        std::thread::spawn(move || {
+            let mut buf = [0; 4096];
+            let mut pending = vec![];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        pending.extend_from_slice(&buf[..n]);
+                        emit(
+                            &app,
+                            TerminalEvent::Output {
+                                session,
+                                data: complete(&mut pending),
+                            },
+                        )
+                    }
+                }
+            }
+            let terminals = app.state::<TerminalState>();
+            block_on(terminals.sessions.lock()).remove(&session);
+            emit(&app, TerminalEvent::Exited { session });
+        });
+
+        Ok(session)
+    }
+
+    pub async fn write(&self, session: u32, data: String) -> Result<(), String> {
+        let writer = match self.sessions.lock().await.get(&session) {
+            Some(t) => t.writer.clone(),
+            None => return Err(format!("no terminal with id {}", session)),
+        };
+        tauri::async_runtime::spawn_blocking(move || {
+            writer
+                .lock()
+                .map_err(|e| e.to_string())?
+                .write_all(data.as_bytes())
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn resize(&self, session: u32, rows: u16, cols: u16) -> Result<(), String> {
+        match self.sessions.lock().await.get(&session) {
+            Some(t) => t
+                .master
+                .resize(PtySize {
+                    rows,
+                    cols,
+                    pixel_width: 0,
+                    pixel_height: 0,
+                })
+                .map_err(|e| e.to_string()),
+            None => Err(format!("no terminal with id {}", session)),
+        }
+    }
+
+    /// kills the exec session, the reader thread notices and reports that it exited
+    pub async fn close(&self, session: u32) {
+        if let Some(mut t) = self.sessions.lock().await.remove(&session) {
+            t.child.kill().ok();
+        }
+    }
+}
+
+/// Takes the text read so far off the buffer, leaving a character that is cut off by the
+/// end of a read for the next one.
+fn complete(pending: &mut Vec<u8>) -> String {
+    let end = match std::str::from_utf8(pending) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => pending.len(),
+    };
+    let rest = pending.split_off(end);
+    let text = String::from_utf8_lossy(pending).to_string();
+    *pending = rest;
+    text
+}
+
+fn emit(app: &AppHandle, event: TerminalEvent) {
+    if let Err(e) = app.emit_all(TerminalEvent::name(), event) {
+        println!("unable to emit terminal event: {}", e);
+    }
+}
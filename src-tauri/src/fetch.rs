@@ -0,0 +1,377 @@
+//! Fetches the team config into the base layer. The source can be a url, a local
+//! file, or a file in a git repository, and what changes in the effective config is
+//! shown before it is written.
+
+use std::{
+    collections::hash_map::RandomState,
+    fs,
+    hash::{BuildHasher, Hasher},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Url,
+};
+use serde_json::Value;
+use tokio::process::Command;
+
+use crate::{
+    format::{self, Format},
+    interpolate, layers, migrate, validate,
+};
+
+/// Where to fetch the config from
+enum Source {
+    Http(Url),
+    File(PathBuf),
+    /// A file in a git repository, at a branch or tag or else the default branch
+    Git {
+        repository: String,
+        reference: Option<String>,
+        path: PathBuf,
+    },
+}
+
+impl Source {
+    /// Reads a source as given on the command line: an http or file url, a path, or
+    /// `git+<repository>#<path>` with an optional `<branch or tag>:` before the path.
+    fn parse(source: &str) -> Result<Self, String> {
+        if let Some(git) = source.strip_prefix("git+") {
+            let (repository, file) = git.rsplit_once('#').ok_or_else(|| {
+                format!(
+                    "{}: a git source needs the path of the config after a #, such as \
+                     git+https://example.com/team/config.git#dashboard.yaml",
+                    source
+                )
+            })?;
+            let (reference, path) = match file.split_once(':') {
+                Some((reference, path)) => (Some(reference.to_string()), path),
+                None => (None, file),
+            };
+            return Ok(Self::Git {
+                repository: repository.to_string(),
+                reference,
+                path: PathBuf::from(path),
+            });
+        }
+
+        match Url::parse(source) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Ok(Self::Http(url)),
+            Ok(url) if url.scheme() == "file" => url
+                .to_file_path()
+                .map(Self::File)
+                .map_err(|_| format!("{}: not a local file", source)),
+            Ok(url) if source.contains("://") => Err(format!(
+                "{}: {} sources are not supported",
+                source,
+                url.scheme()
+            )),
+            // anything else, including windows drive letters, is a path
+            _ => Ok(Self::File(PathBuf::from(source))),
+        }
+    }
+
+    /// The path of the fetched file, to tell its format by
+    fn path(&self) -> PathBuf {
+        match self {
+            Self::Http(url) => PathBuf::from(url.path()),
+            Self::File(path) | Self::Git { path, .. } => path.clone(),
+        }
+    }
+
+    async fn read(&self, headers: &[(HeaderName, HeaderValue)]) -> Result<String, String> {
+        match self {
+            Self::Http(url) => {
+                let headers = headers.iter().cloned().collect::<HeaderMap>();
+                let response = reqwest::Client::new()
+                    .get(url.clone())
+                    .headers(headers)
+                    .send()
+                    .await
+                    .and_then(|r| r.error_for_status())
+                    .map_err(|e| format!("could not fetch config: {}", e))?;
+                response
+                    .text()
+                    .await
+                    .map_err(|e| format!("could not fetch config: {}", e))
+            }
+            Self::File(path) => fs::read_to_string(path)
+                .map_err(|e| format!("could not read config: {}: {}", path.display(), e)),
+            Self::Git {
+                repository,
+                reference,
+                path,
+            } => {
+                let checkout = temp_dir()?;
+                let body = clone(repository, reference.as_deref(), headers, &checkout)
+                    .await
+                    .and_then(|_| {
+                        fs::read_to_string(checkout.join(path)).map_err(|e| {
+                            format!("could not read config: {}: {}", path.display(), e)
+                        })
+                    });
+                let _ = fs::remove_dir_all(&checkout);
+                body
+            }
+        }
+    }
+}
+
+/// Creates a new directory that only we can use, for the checkout and for a dry run.
+fn temp_dir() -> Result<PathBuf, String> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    loop {
+        // hashers are seeded randomly, which is all the randomness needed for a name
+        let suffix = RandomState::new().build_hasher().finish();
+        let dir = std::env::temp_dir().join(format!(
+            "developer-dashboard-{}-{:016x}",
+            std::process::id(),
+            suffix
+        ));
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("could not create a temporary directory: {}", e)),
+        }
+    }
+}
+
+/// Makes a shallow clone of a repository. The headers are sent when it is cloned over
+/// http, passed in the environment rather than on the command line where anyone can see
+/// them.
+async fn clone(
+    repository: &str,
+    reference: Option<&str>,
+    headers: &[(HeaderName, HeaderValue)],
+    into: &Path,
+) -> Result<(), String> {
+    let mut command = Command::new("git");
+    if !headers.is_empty() {
+        command.env("GIT_CONFIG_COUNT", headers.len().to_string());
+    }
+    for (i, (name, value)) in headers.iter().enumerate() {
+        let value = value.to_str().map_err(|e| e.to_string())?;
+        command
+            .env(format!("GIT_CONFIG_KEY_{}", i), "http.extraHeader")
+            .env(
+                format!("GIT_CONFIG_VALUE_{}", i),
+                format!("{}: {}", name, value),
+            );
+    }
+    command.args(["clone", "--quiet", "--depth", "1"]);
+    if let Some(reference) = reference {
+        command.args(["--branch", reference]);
+    }
+    let output = command
+        .arg(repository)
+        .arg(into)
+        .output()
+        .await
+        .map_err(|e| format!("unable to run git: {}", e))?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(format!(
+            "could not clone {}: {}",
+            repository,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+/// Reads a `Name: value` header, where the value can use the same references to the
+/// environment and to secrets as the config.
+fn header(header: &str, dir: &Path) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = header
+        .split_once(':')
+        .ok_or_else(|| format!("{}: a header is written as `Name: value`", header))?;
+    let value = interpolate::string(value.trim(), dir)?;
+    Ok((
+        HeaderName::from_bytes(name.trim().as_bytes()).map_err(|e| format!("{}: {}", name, e))?,
+        HeaderValue::from_str(&value).map_err(|e| format!("{}: {}", name, e))?,
+    ))
+}
+
+/// Fetches the config into the base layer, showing how the effective config changes.
+/// The base is replaced at once, keeping the previous one as a backup, and a running
+/// app picks it up like any other change. A dry run stops after showing the changes.
+pub async fn fetch(
+    source: &str,
+    headers: &[String],
+    dry_run: bool,
+    config_dir: Option<&Path>,
+) -> Result<(), String> {
+    let config_dir = config_dir
+        .ok_or_else(|| "could not locate config path".to_string())?
+        .to_path_buf();
+    // secrets can be read from the keychain, which blocks
+    let (headers, dir) = (headers.to_vec(), config_dir.clone());
+    let headers = tauri::async_runtime::spawn_blocking(move || {
+        headers
+            .iter()
+            .map(|h| header(h, &dir))
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    let location = Source::parse(source)?;
+
+    println!("fetching config from {}", source);
+    let body = location.read(&headers).await?;
+    let fetched = Format::from_path(&location.path()).unwrap_or_else(|| Format::sniff(&body));
+    fetched
+        .parse::<Value>(&body)
+        .map_err(|e| format!("invalid config: {}", e))?;
+
+    // the local config is left as is, overriding the fetched one
+    let base = format::find(&config_dir, layers::BASE)?;
+
+    // an existing base keeps its format, otherwise the fetched one is kept as is so
+    // that any comments in it survive
+    let (config_file, contents) = match base {
+        Some(existing) => {
+            let contents = match Format::from_path(&existing) {
+                Some(format) => convert(body, fetched, format)?,
+                None => body,
+            };
+            (existing, contents)
+        }
+        None => (
+            config_dir.join(format!("{}.{}", layers::BASE, fetched.extension())),
+            body,
+        ),
+    };
+    if fs::read_to_string(&config_file).ok().as_deref() == Some(contents.as_str()) {
+        println!("{} is up to date", config_file.display());
+        return Ok(());
+    }
+
+    // written next to the base under a name that isn't read, with the same extension,
+    // or somewhere else entirely for a dry run, which leaves the config alone
+    let name = config_file
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let (stage, staged) = match dry_run {
+        true => {
+            let dir = temp_dir()?;
+            let staged = dir.join(name.as_ref());
+            (Some(dir), staged)
+        }
+        false => {
+            fs::create_dir_all(&config_dir).map_err(|e| format!("could not save config: {}", e))?;
+            (None, config_dir.join(format!(".{}", name)))
+        }
+    };
+    let changes = match fs::write(&staged, &contents) {
+        // secrets can be read from the keychain, which blocks
+        Ok(()) => {
+            let (dir, staged, config_file) =
+                (config_dir.clone(), staged.clone(), config_file.clone());
+            tauri::async_runtime::spawn_blocking(move || changes(&dir, &staged, &config_file))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|changes| changes)
+        }
+        Err(e) => Err(format!("could not save config: {}", e)),
+    };
+    let cleanup = || match &stage {
+        Some(dir) => fs::remove_dir_all(dir),
+        None => fs::remove_file(&staged),
+    };
+    match changes {
+        Ok(changes) if changes.is_empty() => println!("the effective config is unchanged"),
+        Ok(changes) => print!("{}", changes),
+        Err(e) => {
+            let _ = cleanup();
+            return Err(e);
+        }
+    }
+    if dry_run {
+        let _ = cleanup();
+        return Ok(());
+    }
+
+    if config_file.is_file() {
+        let mut backup = config_file.clone().into_os_string();
+        backup.push(".bak");
+        println!("keeping the previous config as {:?}", backup);
+        fs::copy(&config_file, &backup).map_err(|e| format!("could not back up config: {}", e))?;
+    }
+    println!("saving config to {:?}", config_file);
+    fs::rename(&staged, &config_file).map_err(|e| format!("could not save config: {}", e))?;
+    Ok(())
+}
+
+/// The fetched config in the format of the base it replaces. The file is migrated so
+/// that it can be written in the other format, which keeps every key in it, the ones for
+/// layering included.
+fn convert(body: String, from: Format, to: Format) -> Result<String, String> {
+    if from == to {
+        return Ok(body);
+    }
+    let mut value: Value = from
+        .parse(&body)
+        .map_err(|e| format!("invalid config: {}", e))?;
+    migrate::migrate(&mut value).map_err(|e| format!("invalid config: {}", e))?;
+    to.write(&value)
+}
+
+/// How the effective config changes with the staged base under the local config, which
+/// is checked like a reload would, as it wouldn't be applied otherwise.
+fn changes(dir: &Path, staged: &Path, base: &Path) -> Result<String, String> {
+    // a current config that can't be read is compared as if there was none
+    let before = layers::read(dir).unwrap_or_default();
+    let mut after = layers::read_staged(staged, base)
+        .map_err(|e| format!("the fetched config can't be read: {}", e))?;
+    if let Some(local) = format::find(dir, layers::LOCAL)? {
+        after.extend(layers::read_file(&local)?);
+    }
+    let (_, problems) = validate::load(&after, dir);
+    if !problems.is_empty() {
+        let problems = problems.iter().map(ToString::to_string).collect::<Vec<_>>();
+        return Err(format!(
+            "the fetched config has problems:\n{}",
+            problems.join("\n")
+        ));
+    }
+    Ok(layers::diff(&before, &after))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::convert;
+    use crate::format::Format;
+
+    #[test]
+    fn converting_keeps_the_layering_keys() {
+        let body = r#"{
+            "$schema": "schema.json",
+            "include": ["extra.json"],
+            "tunnels": [{"name": "db", "disabled": true}]
+        }"#;
+        let yaml = convert(body.to_string(), Format::Json, Format::Yaml).unwrap();
+        let value: Value = Format::Yaml.parse(&yaml).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "$schema": "schema.json",
+                "include": ["extra.json"],
+                "tunnels": [{"name": "db", "disabled": true}],
+                "version": 1
+            })
+        );
+    }
+
+    #[test]
+    fn converting_to_the_same_format_keeps_the_file() {
+        let body = "# the team config\ntunnels: []\n";
+        let converted = convert(body.to_string(), Format::Yaml, Format::Yaml).unwrap();
+        assert_eq!(converted, body);
+    }
+}
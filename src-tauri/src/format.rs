@@ -0,0 +1,167 @@
+//! The file formats the config can be written in. The format is picked from the
+//! extension where there is one, and otherwise from the content.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// The schema generated by the build script, written next to the config so that
+/// editors can pick it up with `"$schema": "schema.json"` in json,
+/// `# yaml-language-server: $schema=schema.json` in yaml or `#:schema schema.json`
+/// in toml.
+const SCHEMA: &str = include_str!("../schema.json");
+
+/// The extensions a config file is looked up with, in order of preference.
+const EXTENSIONS: &[&str] = &["json", "yaml", "yml", "toml"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// The format for a path or url path, by its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format of a document without an extension. Json objects start with
+    /// a brace, toml is tried next as yaml would accept most toml documents as a
+    /// plain string.
+    pub fn sniff(body: &str) -> Self {
+        if body.trim_start().starts_with('{') {
+            Self::Json
+        } else if toml::from_str::<toml::Value>(body).is_ok() {
+            Self::Toml
+        } else {
+            Self::Yaml
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Yaml => "yaml",
+            Self::Toml => "toml",
+        }
+    }
+
+    /// Parses a document, with the line and column of a syntax error in front of it
+    /// as `line:column: message`.
+    pub fn parse<T: DeserializeOwned>(self, body: &str) -> Result<T, String> {
+        self.parse_at(body)
+            .map_err(|(position, message)| match position {
+                Some((line, column)) => format!("{}:{}: {}", line, column, message),
+                None => message,
+            })
+    }
+
+    /// Parses a document, with the line and column of a syntax error apart from its
+    /// message.
+    fn parse_at<T: DeserializeOwned>(
+        self,
+        body: &str,
+    ) -> Result<T, (Option<(usize, usize)>, String)> {
+        match self {
+            Self::Json => serde_json::from_str(body).map_err(|e| {
+                let position = (e.line() > 0).then(|| (e.line(), e.column()));
+                located(e.to_string(), position)
+            }),
+            Self::Yaml => serde_yaml::from_str(body).map_err(|e| {
+                let position = e.location().map(|l| (l.line(), l.column()));
+                located(e.to_string(), position)
+            }),
+            // toml counts from zero, but not in its messages
+            Self::Toml => toml::from_str(body).map_err(|e| {
+                let position = e.line_col().map(|(line, column)| (line + 1, column + 1));
+                located(e.to_string(), position)
+            }),
+        }
+    }
+
+    pub fn write<T: Serialize>(self, value: &T) -> Result<String, String> {
+        match self {
+            Self::Json => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+            Self::Yaml => serde_yaml::to_string(value).map_err(|e| e.to_string()),
+            // toml has no null and no newtype variants, so go through json first, and
+            // then through a toml value which puts plain keys before tables
+            Self::Toml => serde_json::to_value(value)
+                .map_err(|e| e.to_string())
+                .and_then(|v| toml::Value::try_from(without_nulls(v)).map_err(|e| e.to_string()))
+                .and_then(|v| toml::to_string_pretty(&v).map_err(|e| e.to_string())),
+        }
+    }
+}
+
+/// Takes out the position the parsers put inside their messages.
+fn located(message: String, position: Option<(usize, usize)>) -> (Option<(usize, usize)>, String) {
+    match position {
+        Some((line, column)) => {
+            let inside = format!(" at line {} column {}", line, column);
+            (position, message.replacen(&inside, "", 1))
+        }
+        None => (None, message),
+    }
+}
+
+fn without_nulls(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .filter(|(_, v)| !v.is_null())
+            .map(|(k, v)| (k, without_nulls(v)))
+            .collect(),
+        serde_json::Value::Array(items) => items.into_iter().map(without_nulls).collect(),
+        value => value,
+    }
+}
+
+/// The config file with the given name in the directory, in any of the formats. More
+/// than one of them is an error, as the others would be ignored without notice.
+pub fn find(dir: &Path, stem: &str) -> Result<Option<PathBuf>, String> {
+    let mut files = EXTENSIONS
+        .iter()
+        .map(|e| dir.join(format!("{}.{}", stem, e)))
+        .filter(|p| p.is_file());
+    match (files.next(), files.next()) {
+        (Some(first), Some(second)) => Err(format!(
+            "{} and {} are both there, only one of them can be read",
+            first.display(),
+            second.display()
+        )),
+        (file, _) => Ok(file),
+    }
+}
+
+/// Parses the contents of a config file, in the format of its name. A syntax error is
+/// reported as `file:line:column: message`.
+pub fn parse<T: DeserializeOwned>(path: &Path, body: &str) -> Result<T, String> {
+    Format::from_path(path)
+        .unwrap_or_else(|| Format::sniff(body))
+        .parse_at(body)
+        .map_err(|(position, message)| match position {
+            Some((line, column)) => format!("{}:{}:{}: {}", path.display(), line, column, message),
+            None => format!("{}: {}", path.display(), message),
+        })
+}
+
+/// Writes the schema next to the config, unless it is already up to date.
+pub fn write_schema(dir: &Path) {
+    let path = dir.join("schema.json");
+    if fs::read_to_string(&path).ok().as_deref() == Some(SCHEMA) {
+        return;
+    }
+    if let Err(e) = fs::create_dir_all(dir).and_then(|_| fs::write(&path, SCHEMA)) {
+        println!("unable to write the config schema: {}", e);
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::{HashMap, HashSet};
+
+use commands::{DependencyGraph, GraphNode, NodeKind, PodmanState};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    config::{Dependency, ServiceSection},
+    health::HealthState,
+    podman::ContainerState,
+    SSHTunnelState,
+};
+
+/// a short description of a dependency, such as `tunnel db`
+pub fn label(dependency: &Dependency) -> String {
+    match dependency {
+        Dependency::Tunnel(name) => format!("tunnel {}", name),
+        Dependency::Container(name) => format!("container {}", name),
+        Dependency::Service(name) => format!("service {}", name),
+    }
+}
+
+fn id(dependency: &Dependency) -> String {
+    match dependency {
+        Dependency::Tunnel(name) => format!("tunnel:{}", name),
+        Dependency::Container(name) => format!("container:{}", name),
+        Dependency::Service(name) => format!("service:{}", name),
+    }
+}
+
+/// whether a dependency is up, along with what blocks it if it is a blocked service.
+/// services that haven't been checked yet are neither up nor down.
+pub async fn status(app: &AppHandle, dependency: &Dependency) -> (Option<bool>, Option<String>) {
+    match dependency {
+        Dependency::Tunnel(name) => {
+            let state = app.state::<SSHTunnelState>();
+            let mut tunnels = state.0.lock().await;
+            let up = tunnels
+                .0
+                .iter_mut()
+                .find(|t| &t.tunnel.name == name)
+                .map_or(false, |t| t.connected());
+            (Some(up), None)
+        }
+        Dependency::Container(name) => {
+            let up = match &*app.state::<ContainerState>().containers.lock().await {
+                Ok(containers) => containers
+                    .values()
+                    .any(|c| &c.name == name && c.state == PodmanState::Running),
+                Err(_) => false,
+            };
+            (Some(up), None)
+        }
+        Dependency::Service(name) => match app.state::<HealthState>().result(name).await {
+            Some(result) => (Some(result.up), result.blocked_by),
+            None => (None, None),
+        },
+    }
+}
+
+/// whether each of the dependencies is up, see [status]
+pub async fn statuses(app: &AppHandle, dependencies: &[Dependency]) -> Vec<Option<bool>> {
+    let mut statuses = Vec::with_capacity(dependencies.len());
+    for dependency in dependencies {
+        statuses.push(status(app, dependency).await.0);
+    }
+    statuses
+}
+
+/// the first dependency that is down, or rather what is at the root of it being down
+pub async fn blocked_by(app: &AppHandle, dependencies: &[Dependency]) -> Option<String> {
+    for dependency in dependencies {
+        if let (Some(false), blocked_by) = status(app, dependency).await {
+            return Some(blocked_by.unwrap_or_else(|| label(dependency)));
+        }
+    }
+    None
+}
+
+/// the services that depend on themselves through other services, which can't be
+/// checked in dependency order
+pub fn cycles(sections: &[ServiceSection]) -> HashSet<String> {
+    let edges = sections
+        .iter()
+        .flat_map(|s| &s.services)
+        .map(|s| {
+            let dependencies = s
+                .depends_on
+                .iter()
+                .filter_map(|d| match d {
+                    Dependency::Service(name) => Some(name.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            (s.name.as_str(), dependencies)
+        })
+        .collect::<HashMap<_, _>>();
+
+    edges
+        .keys()
+        .filter(|start| {
+            // walk the dependencies, looking for the way back to the start
+            let mut seen = HashSet::new();
+            let mut queue = edges[*start].clone();
+            while let Some(name) = queue.pop() {
+                if name == **start {
+                    return true;
+                }
+                if seen.insert(name) {
+                    queue.extend(edges.get(name).into_iter().flatten());
+                }
+            }
+            false
+        })
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// the services, everything they depend on and how they are doing right now
+pub async fn graph(app: &AppHandle) -> DependencyGraph {
+    let sections = app.state::<HealthState>().sections().await;
+    let mut graph = DependencyGraph::default();
+    let mut seen = HashSet::new();
+
+    for service in sections.iter().flat_map(|s| &s.services) {
+        let node = Dependency::Service(service.name.clone());
+        for dependency in &service.depends_on {
+            graph.edges.push((id(&node), id(dependency)));
+        }
+        for dependency in std::iter::once(&node).chain(&service.depends_on) {
+            if !seen.insert(id(dependency)) {
+                continue;
+            }
+            let (up, blocked_by) = status(app, dependency).await;
+            let (kind, name) = match dependency {
+                Dependency::Service(name) => (NodeKind::Service, name),
+                Dependency::Tunnel(name) => (NodeKind::Tunnel, name),
+                Dependency::Container(name) => (NodeKind::Container, name),
+            };
+            graph.nodes.push(GraphNode {
+                id: id(dependency),
+                kind,
+                name: name.clone(),
+                up,
+                blocked_by,
+            });
+        }
+    }
+
+    graph
+}
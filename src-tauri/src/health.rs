@@ -0,0 +1,315 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use commands::{
+    Event, HealthSample, HealthcheckEvent, HealthcheckSection, NotifyItem, ServiceHealthCheck,
+};
+use futures::future::{select, Either};
+use tauri::{
+    async_runtime::{JoinHandle, Mutex},
+    AppHandle, Manager,
+};
+use tokio::sync::Notify;
+
+use crate::{
+    check::Checker,
+    config::{Dependency, Service, ServiceSection},
+    graph,
+    history::History,
+    notify::Notifier,
+};
+
+/// How often to check a service, unless configured
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How much the latency has to change by for a result that is otherwise the same to be
+/// pushed to the frontend
+const LATENCY_CHANGE_MS: u64 = 50;
+
+/// How often to delete the history that is past its retention
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The latest healthcheck results, kept up to date by a background scheduler
+pub struct HealthState {
+    sections: Mutex<Vec<ServiceSection>>,
+    /// keyed by section and service name
+    results: Mutex<HashMap<(String, String), ServiceHealthCheck>>,
+    /// the running checks, keyed like the results
+    tasks: Mutex<HashMap<(String, String), Task>>,
+    checker: Checker,
+    history: History,
+    /// woken whenever something a service may depend on changes
+    changed: Notify,
+}
+
+/// The checks of a service, with the config and ordering they were started with
+struct Task {
+    service: Service,
+    ordered: bool,
+    handle: JoinHandle<()>,
+}
+
+impl HealthState {
+    /// `base` is the directory the paths in the config are relative to
+    pub fn new(sections: Vec<ServiceSection>, history: History, base: Option<PathBuf>) -> Self {
+        Self {
+            sections: Mutex::new(sections),
+            results: Mutex::new(HashMap::new()),
+            tasks: Mutex::new(HashMap::new()),
+            checker: Checker::new(base),
+            history,
+            changed: Notify::new(),
+        }
+    }
+
+    pub async fn sections(&self) -> Vec<ServiceSection> {
+        self.sections.lock().await.clone()
+    }
+
+    /// the latest result of a service, by name
+    pub async fn result(&self, name: &str) -> Option<ServiceHealthCheck> {
+        self.results
+            .lock()
+            .await
+            .iter()
+            .find(|((_, service), _)| service == name)
+            .map(|(_, result)| result.clone())
+    }
+
+    /// lets the services re-evaluate their dependencies, after a tunnel or container
+    /// changed for example
+    pub fn wake(&self) {
+        self.changed.notify_waiters();
+    }
+
+    /// the cached results in config order, leaving out services that haven't been
+    /// checked yet
+    pub async fn snapshot(&self) -> Vec<HealthcheckSection> {
+        let results = self.results.lock().await;
+        self.sections
+            .lock()
+            .await
+            .iter()
+            .map(|section| HealthcheckSection {
+                name: section.name.clone(),
+                services: section
+                    .services
+                    .iter()
+                    .filter_map(|s| results.get(&(section.name.clone(), s.name.clone())))
+                    .cloned()
+                    .collect(),
+            })
+            .collect()
+    }
+
+    pub async fn history(
+        &self,
+        section: &str,
+        service: &str,
+        since: u64,
+    ) -> Result<Vec<HealthSample>, String> {
+        self.history.query(section, service, since).await
+    }
+}
+
+/// checks every service on its own interval, pushing results that changed to the
+/// frontend. services are checked after what they depend on, and again as soon as that
+/// changes.
+pub async fn watch(app: AppHandle) {
+    let state = app.state::<HealthState>();
+    apply(&app, state.sections().await).await;
+    prune(&state).await;
+}
+
+/// schedules the checks for a new set of sections. services whose config didn't change
+/// keep running undisturbed, changed ones are restarted and removed ones are stopped.
+pub async fn apply(app: &AppHandle, sections: Vec<ServiceSection>) {
+    let state = app.state::<HealthState>();
+    let cycles = graph::cycles(&sections);
+    for name in &cycles {
+        println!(
+            "service {} depends on itself, ignoring the order of its checks",
+            name
+        );
+    }
+    *state.sections.lock().await = sections.clone();
+    state.checker.clear().await;
+
+    let mut tasks = state.tasks.lock().await;
+    let mut previous = std::mem::take(&mut *tasks);
+    for section in sections {
+        for service in section.services {
+            let key = (section.name.clone(), service.name.clone());
+            let ordered = !cycles.contains(&service.name);
+            let task = match previous.remove(&key) {
+                Some(task) if task.service == service && task.ordered == ordered => task,
+                old => {
+                    if let Some(old) = old {
+                        old.handle.abort();
+                    }
+                    let handle = tauri::async_runtime::spawn(watch_service(
+                        app.clone(),
+                        section.name.clone(),
+                        service.clone(),
+                        ordered,
+                    ));
+                    Task {
+                        service,
+                        ordered,
+                        handle,
+                    }
+                }
+            };
+            tasks.insert(key, task);
+        }
+    }
+    drop(tasks);
+
+    let notifier = app.state::<Notifier>();
+    for ((section, name), task) in previous {
+        task.handle.abort();
+        state
+            .results
+            .lock()
+            .await
+            .remove(&(section.clone(), name.clone()));
+        notifier
+            .forget(&NotifyItem::Service { section, name })
+            .await;
+    }
+    state.wake();
+}
+
+/// keeps the history within its retention period
+async fn prune(state: &HealthState) {
+    loop {
+        if let Err(e) = state.history.prune().await {
+            println!("unable to prune healthcheck history: {}", e);
+        }
+        tokio::time::sleep(PRUNE_INTERVAL).await;
+    }
+}
+
+async fn watch_service(app: AppHandle, section: String, service: Service, ordered: bool) {
+    let app = &app;
+    let state = app.state::<HealthState>();
+    let interval = service
+        .interval_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_INTERVAL);
+    let key = (section.clone(), service.name.clone());
+    let dependencies = &service.depends_on;
+
+    // wait for the services this one depends on to be checked first, other than those
+    // that aren't in the config and would never be
+    if ordered {
+        let known = {
+            let sections = state.sections.lock().await;
+            dependencies
+                .iter()
+                .filter(|dependency| match dependency {
+                    Dependency::Service(name) => sections
+                        .iter()
+                        .any(|s| s.services.iter().any(|s| &s.name == name)),
+                    _ => true,
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        loop {
+            let changed = state.changed.notified();
+            let statuses = graph::statuses(app, &known).await;
+            if statuses.iter().all(Option::is_some) {
+                break;
+            }
+            changed.await;
+        }
+    }
+
+    loop {
+        let result = match graph::blocked_by(app, dependencies).await {
+            Some(blocked_by) => ServiceHealthCheck {
+                name: service.name.clone(),
+                url: service.url.to_string(),
+                up: false,
+                reason: Some(format!("blocked by {}", blocked_by)),
+                latency_ms: 0,
+                status: None,
+                components: vec![],
+                build: None,
+                blocked_by: Some(blocked_by),
+            },
+            None => state.checker.run(service.clone()).await,
+        };
+        if let Err(e) = state.history.record(&section, &result).await {
+            println!("unable to record healthcheck of {}: {}", service.name, e);
+        }
+        // the root cause notifies for the services it blocks
+        if result.blocked_by.is_none() {
+            let item = NotifyItem::Service {
+                section: section.clone(),
+                name: service.name.clone(),
+            };
+            app.state::<Notifier>()
+                .observe(app, item, result.up, result.reason.clone())
+                .await;
+        }
+        // a result that only differs by a little latency isn't pushed and isn't kept
+        // either, so that small changes add up until they are pushed
+        let changed = {
+            let mut results = state.results.lock().await;
+            let changed = !results.get(&key).map_or(false, |p| same(p, &result));
+            if changed {
+                results.insert(key.clone(), result.clone());
+            }
+            changed
+        };
+
+        if changed {
+            let event = HealthcheckEvent {
+                section: section.clone(),
+                service: result,
+            };
+            if let Err(e) = app.emit_all(HealthcheckEvent::name(), event) {
+                println!("unable to emit healthcheck event: {}", e);
+            }
+            state.wake();
+        }
+
+        sleep(app, &state, dependencies, interval).await;
+    }
+}
+
+/// waits for the next check, which is early when one of the dependencies goes up or down
+async fn sleep(
+    app: &AppHandle,
+    state: &HealthState,
+    dependencies: &[Dependency],
+    interval: Duration,
+) {
+    let mut timer = Box::pin(tokio::time::sleep(interval));
+    if dependencies.is_empty() {
+        return timer.await;
+    }
+
+    let before = graph::statuses(app, dependencies).await;
+    loop {
+        let changed = Box::pin(state.changed.notified());
+        if let Either::Left(_) = select(&mut timer, changed).await {
+            return;
+        }
+        if graph::statuses(app, dependencies).await != before {
+            return;
+        }
+    }
+}
+
+/// whether two results are the same apart from a small change in latency, which
+/// changes a little every time
+fn same(a: &ServiceHealthCheck, b: &ServiceHealthCheck) -> bool {
+    let latency = a.latency_ms.max(b.latency_ms) - a.latency_ms.min(b.latency_ms);
+    latency < LATENCY_CHANGE_MS
+        && a == &ServiceHealthCheck {
+            latency_ms: a.latency_ms,
+            ..b.clone()
+        }
+}
@@ -0,0 +1,138 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use commands::{HealthSample, ServiceHealthCheck};
+use rusqlite::{params, Connection};
+
+/// How many days to keep healthcheck results around, unless configured
+const DEFAULT_RETENTION_DAYS: u64 = 7;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS healthchecks (
+        section TEXT NOT NULL,
+        service TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        up INTEGER NOT NULL,
+        latency_ms INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS healthchecks_service
+        ON healthchecks (section, service, timestamp);
+";
+
+/// The past healthcheck results, stored in a small sqlite database
+pub struct History {
+    /// sqlite blocks, so the connection is only used on blocking threads
+    conn: Arc<Mutex<Connection>>,
+    /// how long to keep results around
+    retention: Duration,
+}
+
+impl History {
+    /// opens the database in the given directory, falling back to one in memory so that
+    /// the history at least covers the current session
+    pub fn open(dir: Option<&Path>, retention_days: Option<u64>) -> Self {
+        let conn = dir
+            .ok_or_else(|| "could not locate data dir".to_string())
+            .and_then(|dir| {
+                std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                let conn =
+                    Connection::open(dir.join("history.sqlite")).map_err(|e| e.to_string())?;
+                conn.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+                Ok(conn)
+            })
+            .unwrap_or_else(|e| {
+                println!(
+                    "unable to open healthcheck history, keeping it in memory: {}",
+                    e
+                );
+                let conn = Connection::open_in_memory().expect("sqlite can always open in memory");
+                conn.execute_batch(SCHEMA).expect("the schema is static");
+                conn
+            });
+        let days = retention_days.unwrap_or(DEFAULT_RETENTION_DAYS);
+        Self {
+            conn: Arc::new(Mutex::new(conn)),
+            retention: Duration::from_secs(days * 24 * 60 * 60),
+        }
+    }
+
+    /// runs a query on a blocking thread
+    async fn run<T, F>(&self, query: F) -> Result<T, String>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let conn = conn.lock().map_err(|e| e.to_string())?;
+            query(&conn).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn record(&self, section: &str, result: &ServiceHealthCheck) -> Result<(), String> {
+        let (section, service) = (section.to_string(), result.name.clone());
+        let (up, latency_ms) = (result.up, result.latency_ms);
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO healthchecks (section, service, timestamp, up, latency_ms)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![section, service, now_ms(), up, latency_ms],
+            )
+            .map(|_| ())
+        })
+        .await
+    }
+
+    /// the results of a service since the given unix timestamp in milliseconds, oldest
+    /// first
+    pub async fn query(
+        &self,
+        section: &str,
+        service: &str,
+        since: u64,
+    ) -> Result<Vec<HealthSample>, String> {
+        let (section, service) = (section.to_string(), service.to_string());
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT timestamp, up, latency_ms FROM healthchecks
+                    WHERE section = ?1 AND service = ?2 AND timestamp >= ?3
+                    ORDER BY timestamp",
+            )?;
+            let samples = stmt
+                .query_map(params![section, service, since], |row| {
+                    Ok(HealthSample {
+                        timestamp: row.get(0)?,
+                        up: row.get(1)?,
+                        latency_ms: row.get(2)?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>();
+            samples
+        })
+        .await
+    }
+
+    /// deletes the results that are older than the retention period
+    pub async fn prune(&self) -> Result<usize, String> {
+        let cutoff = now_ms().saturating_sub(self.retention.as_millis() as u64);
+        self.run(move |conn| {
+            conn.execute(
+                "DELETE FROM healthchecks WHERE timestamp < ?1",
+                params![cutoff],
+            )
+        })
+        .await
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
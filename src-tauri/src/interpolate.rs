@@ -0,0 +1,160 @@
+//! Fills in the values that can't be committed to a shared config when it is loaded.
+//! Every string can use `${VAR}` and `${VAR:-default}` to read the environment, with
+//! `$${` for a literal `${`, and a string that is just `secret://name` is replaced by the
+//! secret with that name, from the local secret file or else the keychain.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde_json::Value;
+
+use crate::{auth, format, validate::Step};
+
+/// The name of the local secret file next to the config, a map of names to secrets in
+/// any of the config formats
+const SECRETS: &str = "secrets";
+
+/// The keychain service secrets are read from, unless the name is `service/account`
+const KEYCHAIN_SERVICE: &str = "developer-dashboard";
+
+const SCHEME: &str = "secret://";
+
+/// Resolves every reference in the config, returning the ones that can't be resolved
+/// with where they are.
+pub fn resolve(value: &mut Value, dir: &Path) -> Vec<(Vec<Step>, String)> {
+    let mut resolver = Resolver {
+        dir,
+        secrets: None,
+        errors: vec![],
+    };
+    resolver.value(value, vec![]);
+    resolver.errors
+}
+
+/// Resolves the references in a single string, such as a header given on the command
+/// line.
+pub fn string(text: &str, dir: &Path) -> Result<String, String> {
+    let mut resolver = Resolver {
+        dir,
+        secrets: None,
+        errors: vec![],
+    };
+    resolver.string(text)
+}
+
+struct Resolver<'a> {
+    dir: &'a Path,
+    /// the secret file, read the first time a secret is needed
+    secrets: Option<Result<BTreeMap<String, String>, String>>,
+    errors: Vec<(Vec<Step>, String)>,
+}
+
+impl Resolver<'_> {
+    fn value(&mut self, value: &mut Value, path: Vec<Step>) {
+        match value {
+            Value::String(text) => match self.string(text) {
+                Ok(resolved) => *text = resolved,
+                Err(e) => self.errors.push((path, e)),
+            },
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    let mut path = path.clone();
+                    path.push(Step::Key(key.clone()));
+                    self.value(value, path);
+                }
+            }
+            Value::Array(entries) => {
+                for (i, entry) in entries.iter_mut().enumerate() {
+                    let mut path = path.clone();
+                    path.push(Step::entry(entry, i));
+                    self.value(entry, path);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn string(&mut self, text: &str) -> Result<String, String> {
+        let text = env(text)?;
+        match text.strip_prefix(SCHEME) {
+            Some(name) => self.secret(name),
+            None => Ok(text),
+        }
+    }
+
+    fn secret(&mut self, name: &str) -> Result<String, String> {
+        let dir = self.dir;
+        let secrets = self.secrets.get_or_insert_with(|| read_secrets(dir));
+        // why it isn't in the secret file, to report if it isn't in the keychain either
+        let missing = match secrets {
+            Ok(secrets) => match secrets.get(name) {
+                Some(secret) => return Ok(secret.clone()),
+                None => "it is not in the secret file".to_string(),
+            },
+            Err(e) => e.clone(),
+        };
+
+        let (service, account) = match name.split_once('/') {
+            Some((service, account)) => (service, account),
+            None => (KEYCHAIN_SERVICE, name),
+        };
+        auth::keychain_blocking(service, account)
+            .map_err(|keychain| format!("{}{}: {}, and {}", SCHEME, name, missing, keychain))
+    }
+}
+
+/// The contents of the secret file in the directory, to tell when it changes.
+pub fn secrets(dir: &Path) -> Option<String> {
+    format::find(dir, SECRETS)
+        .ok()
+        .flatten()
+        .and_then(|file| fs::read_to_string(file).ok())
+}
+
+/// The secret file in the directory, which is empty when there is none.
+fn read_secrets(dir: &Path) -> Result<BTreeMap<String, String>, String> {
+    let file = match format::find(dir, SECRETS)? {
+        Some(file) => file,
+        None => return Ok(BTreeMap::new()),
+    };
+    let body = fs::read_to_string(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+    format::parse(&file, &body)
+}
+
+/// Substitutes the environment variables in a string. Like in a shell, the default is
+/// used when the variable is unset or empty.
+fn env(text: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            out.push_str("${");
+            rest = after;
+            continue;
+        }
+        if !rest.starts_with("${") {
+            out.push('$');
+            rest = &rest[1..];
+            continue;
+        }
+
+        let end = rest
+            .find('}')
+            .ok_or_else(|| format!("{} is missing a closing brace", rest))?;
+        let expression = &rest[2..end];
+        let (name, default) = match expression.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expression, None),
+        };
+        match (std::env::var(name), default) {
+            (Ok(value), Some(default)) if value.is_empty() => out.push_str(default),
+            (Ok(value), _) => out.push_str(&value),
+            (Err(_), Some(default)) => out.push_str(default),
+            (Err(_), None) => return Err(format!("${{{}}} is not set", name)),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
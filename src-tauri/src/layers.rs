@@ -0,0 +1,245 @@
+//! The config is assembled from layers: the team base written by `fetch`, then the
+//! user's own config on top of it, each preceded by the files it includes.
+//!
+//! Objects are merged key by key, and lists of entries with a `name`, such as tunnels
+//! and services, are merged entry by entry. A later layer can add an entry, change the
+//! fields of an entry with the same name, or remove it with `disabled: true`. A `null`
+//! removes a key, and anything else is replaced.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+
+use crate::{format, migrate};
+
+/// The name of the team config, as fetched
+pub const BASE: &str = "base";
+
+/// The name of the user's own config, which overrides the base
+pub const LOCAL: &str = "config";
+
+/// A config file, parsed but not yet merged
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    pub file: PathBuf,
+    /// the version of the config the file is written for, before it was migrated
+    pub version: u32,
+    pub value: Value,
+}
+
+/// Reads the layers in the directory in the order they apply, each file after the
+/// ones it includes.
+pub fn read(dir: &Path) -> Result<Vec<Layer>, String> {
+    let mut layers = vec![];
+    for stem in [BASE, LOCAL] {
+        if let Some(file) = format::find(dir, stem)? {
+            include(&file, &mut vec![], &mut layers)?;
+        }
+    }
+    Ok(layers)
+}
+
+/// Reads a single file as the only layer, after the files it includes.
+pub fn read_file(file: &Path) -> Result<Vec<Layer>, String> {
+    let mut layers = vec![];
+    include(file, &mut vec![], &mut layers)?;
+    Ok(layers)
+}
+
+/// Reads a file that is staged elsewhere as if it was already in place, so that what it
+/// includes is found next to where it is going.
+pub fn read_staged(staged: &Path, file: &Path) -> Result<Vec<Layer>, String> {
+    let canonical = fs::canonicalize(staged).map_err(|e| format!("{}: {}", staged.display(), e))?;
+    let body = fs::read_to_string(staged).map_err(|e| format!("{}: {}", staged.display(), e))?;
+    let mut layers = vec![];
+    layer(file, canonical, &body, &mut vec![], &mut layers)?;
+    Ok(layers)
+}
+
+/// Reads a file and what it includes, where `parents` are the files including it.
+fn include(file: &Path, parents: &mut Vec<PathBuf>, layers: &mut Vec<Layer>) -> Result<(), String> {
+    let canonical = fs::canonicalize(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+    if parents.contains(&canonical) {
+        return Err(format!("{} includes itself", file.display()));
+    }
+    let body = fs::read_to_string(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+    layer(file, canonical, &body, parents, layers)
+}
+
+/// Parses the contents of a file, after the files it includes.
+fn layer(
+    file: &Path,
+    canonical: PathBuf,
+    body: &str,
+    parents: &mut Vec<PathBuf>,
+    layers: &mut Vec<Layer>,
+) -> Result<(), String> {
+    let mut value: Value = format::parse(file, body)?;
+    let version = migrate::migrate(&mut value).map_err(|e| format!("{}: {}", file.display(), e))?;
+
+    // the schema is only there for editors
+    if let Some(object) = value.as_object_mut() {
+        object.remove("$schema");
+    }
+    let includes = match value.as_object_mut().and_then(|o| o.remove("include")) {
+        Some(include) => serde_json::from_value::<Vec<PathBuf>>(include)
+            .map_err(|e| format!("{}: include: {}", file.display(), e))?,
+        None => vec![],
+    };
+    // included paths are relative to the file including them
+    let dir = file.parent().unwrap_or_else(|| Path::new("."));
+    parents.push(canonical);
+    for path in includes {
+        include(&dir.join(path), parents, layers)?;
+    }
+    parents.pop();
+
+    layers.push(Layer {
+        file: file.to_owned(),
+        version,
+        value,
+    });
+    Ok(())
+}
+
+/// The effective config, before it is resolved and checked.
+pub fn merge(layers: &[Layer]) -> Value {
+    let mut merged = Value::Object(Map::new());
+    for layer in layers {
+        apply(&mut merged, layer.value.clone());
+    }
+    merged
+}
+
+fn apply(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                if value.is_null() {
+                    base.remove(&key);
+                } else if let Some(existing) = base.get_mut(&key) {
+                    apply(existing, value);
+                } else {
+                    base.insert(key, fresh(value));
+                }
+            }
+        }
+        (Value::Array(base), Value::Array(layer)) if named(base) && named(&layer) => {
+            for mut entry in layer {
+                let position = base.iter().position(|e| e["name"] == entry["name"]);
+                let disabled = entry
+                    .as_object_mut()
+                    .and_then(|e| e.remove("disabled"))
+                    .map_or(false, |d| d == Value::Bool(true));
+                match (position, disabled) {
+                    (Some(i), true) => {
+                        base.remove(i);
+                    }
+                    (Some(i), false) => apply(&mut base[i], entry),
+                    (None, true) => {}
+                    (None, false) => base.push(fresh(entry)),
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+/// A value that is new in a layer, with the nulls and disabled entries inside it
+/// taken out as if it was merged onto nothing.
+fn fresh(value: Value) -> Value {
+    let mut empty = match &value {
+        Value::Object(_) => Value::Object(Map::new()),
+        Value::Array(_) => Value::Array(vec![]),
+        _ => return value,
+    };
+    apply(&mut empty, value);
+    empty
+}
+
+/// Whether a list is made of named entries, which are merged by name.
+pub fn named(entries: &[Value]) -> bool {
+    entries
+        .iter()
+        .all(|e| e.get("name").map_or(false, Value::is_string))
+}
+
+/// Every value of the effective config, with the file it comes from.
+fn origins(layers: &[Layer]) -> Vec<(String, Value, &Path)> {
+    let flattened = layers
+        .iter()
+        .map(|l| {
+            let mut values = vec![];
+            flatten(&l.value, String::new(), &mut values);
+            values
+        })
+        .collect::<Vec<_>>();
+
+    let mut values = vec![];
+    flatten(&merge(layers), String::new(), &mut values);
+    values
+        .into_iter()
+        .filter_map(|(path, value)| {
+            let layer = flattened
+                .iter()
+                .rposition(|values| values.iter().any(|(p, _)| *p == path))?;
+            Some((path, value, layers[layer].file.as_path()))
+        })
+        .collect()
+}
+
+/// Lists the values in a tree by their path, with named entries identified by name.
+fn flatten(value: &Value, path: String, out: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let path = match path.as_str() {
+                    "" => key.clone(),
+                    _ => format!("{}.{}", path, key),
+                };
+                flatten(value, path, out);
+            }
+        }
+        Value::Array(entries) if !entries.is_empty() && named(entries) => {
+            for entry in entries {
+                let name = entry["name"].as_str().unwrap_or_default();
+                flatten(entry, format!("{}[{}]", path, name), out);
+            }
+        }
+        value => out.push((path, value.clone())),
+    }
+}
+
+/// The effective config as one line per value, followed by the file it comes from.
+pub fn describe(layers: &[Layer]) -> String {
+    origins(layers)
+        .into_iter()
+        .map(|(path, value, file)| format!("{} = {}  # {}\n", path, value, file.display()))
+        .collect()
+}
+
+/// The values that differ between two configs, as lines removing the old value and
+/// adding the new one.
+pub fn diff(before: &[Layer], after: &[Layer]) -> String {
+    let (mut old, mut new) = (vec![], vec![]);
+    flatten(&merge(before), String::new(), &mut old);
+    flatten(&merge(after), String::new(), &mut new);
+
+    let mut out = String::new();
+    for (path, value) in &old {
+        match new.iter().find(|(p, _)| p == path) {
+            Some((_, v)) if v == value => {}
+            Some((_, v)) => out.push_str(&format!("- {} = {}\n+ {} = {}\n", path, value, path, v)),
+            None => out.push_str(&format!("- {} = {}\n", path, value)),
+        }
+    }
+    for (path, value) in &new {
+        if !old.iter().any(|(p, _)| p == path) {
+            out.push_str(&format!("+ {} = {}\n", path, value));
+        }
+    }
+    out
+}
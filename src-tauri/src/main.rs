@@ -3,30 +3,51 @@
     windows_subsystem = "windows"
 )]
 
+mod auth;
+mod check;
 mod config;
+mod exec;
+mod fetch;
+mod format;
+mod graph;
+mod health;
+mod history;
+mod interpolate;
+mod layers;
 #[cfg(all(feature = "cocoa", target_os = "macos"))]
 mod macos;
+mod metrics;
+mod migrate;
+mod notify;
+mod podman;
+mod registry;
+mod reload;
 mod spring;
+mod stack;
+mod storage;
 mod tunnel;
+mod update;
+mod validate;
 
-use std::{fs::File, path::PathBuf};
+use std::path::{Path, PathBuf};
 
 use commands::{
-    HealthcheckSection, ListContainerItem, ListContainerResponse, ListTunnelResponse, PodmanState,
-    ServiceHealthCheck, TunnelState, TunnelStatus,
+    ActionResponse, ConfigStatus, ContainerAction, DependencyGraph, DiskUsageResponse,
+    ExecResponse, HealthHistoryResponse, HealthcheckSection, ListContainerResponse,
+    ListTunnelResponse, NotifyItem, OpenTerminalResponse, PodAction, PreviewPruneResponse,
+    PruneResponse, PruneTarget, StackAction, StackItem, TunnelState, TunnelStatus,
 };
-use config::ServiceSection;
 use directories::ProjectDirs;
-use futures::future::join_all;
-use podman_api::{
-    opts::{ContainerListFilter, ContainerListOpts},
-    Podman,
-};
-use reqwest::StatusCode;
+use exec::TerminalState;
+use health::HealthState;
+use history::History;
+use notify::Notifier;
+use podman::ContainerState;
+use reload::ConfigState;
 use serde::{Deserialize, Serialize};
-use spring::SpringHealthCheck;
+use stack::StackState;
 use tauri::{
-    api::cli::{Matches, SubcommandMatches},
+    api::cli::{get_matches, Matches, SubcommandMatches},
     async_runtime::{block_on, JoinHandle, Mutex},
     Manager,
 };
@@ -34,11 +55,11 @@ use tokio::join;
 use tokio_util::sync::CancellationToken;
 use tunnel::Tunnel;
 
-use crate::config::Config;
-
 struct SSHTunnelConnection {
     task: Option<(CancellationToken, JoinHandle<()>)>,
     tunnel: tunnel::Tunnel,
+    /// how many times the tunnel was connected
+    connects: u64,
 }
 
 impl SSHTunnelConnection {
@@ -54,15 +75,30 @@ impl SSHTunnelConnection {
         self.task.is_some()
     }
 
-    fn connect(&mut self) {
+    fn connect(&mut self, app: &tauri::AppHandle) {
         let token = CancellationToken::new();
         if !self.connected() {
             // this is ok0
             let tunnel = self.tunnel.clone();
             let inner_token = token.clone();
-            let handle =
-                tauri::async_runtime::spawn(async move { tunnel.open(Some(inner_token)).await });
+            let app = app.clone();
+            let handle = tauri::async_runtime::spawn(async move {
+                let notifier = app.state::<Notifier>();
+                let item = NotifyItem::Tunnel(tunnel.name.clone());
+                notifier.forget(&item).await;
+                notifier.observe(&app, item.clone(), true, None).await;
+                app.state::<HealthState>().wake();
+                match tunnel.open(Some(inner_token)).await {
+                    true => {
+                        let reason = Some("the ssh connection closed".to_string());
+                        notifier.observe(&app, item, false, reason).await
+                    }
+                    false => notifier.forget(&item).await,
+                }
+                app.state::<HealthState>().wake();
+            });
             self.task.replace((token, handle));
+            self.connects += 1;
         }
     }
 
@@ -72,6 +108,14 @@ impl SSHTunnelConnection {
         }
     }
 
+    /// disconnects and waits for ssh to exit, so that the local port is free again
+    async fn close(&mut self) {
+        if let Some((token, handle)) = self.task.take() {
+            token.cancel();
+            let _ = handle.await;
+        }
+    }
+
     fn cancel(&mut self) -> bool {
         if let Some((token, _)) = &self.task {
             token.cancel();
@@ -83,76 +127,229 @@ impl SSHTunnelConnection {
 
 impl From<Tunnel> for SSHTunnelConnection {
     fn from(tunnel: Tunnel) -> Self {
-        Self { task: None, tunnel }
+        Self {
+            task: None,
+            tunnel,
+            connects: 0,
+        }
     }
 }
 
 struct SSHTunnels(Vec<SSHTunnelConnection>);
+
+impl SSHTunnels {
+    /// replaces the tunnels with the ones from a new config. tunnels that didn't change
+    /// stay connected, changed ones are reconnected if they were and removed ones are
+    /// closed.
+    async fn reconcile(&mut self, app: &tauri::AppHandle, tunnels: Vec<Tunnel>) {
+        let mut previous = std::mem::take(&mut self.0);
+        for tunnel in tunnels {
+            let connection = match previous.iter().position(|c| c.tunnel.name == tunnel.name) {
+                Some(i) => {
+                    let mut connection = previous.remove(i);
+                    if connection.tunnel != tunnel {
+                        let connected = connection.connected();
+                        connection.close().await;
+                        connection.tunnel = tunnel;
+                        if connected {
+                            connection.connect(app);
+                        }
+                    }
+                    connection
+                }
+                None => tunnel.into(),
+            };
+            self.0.push(connection);
+        }
+        for mut connection in previous {
+            connection.close().await;
+        }
+    }
+}
 struct SSHTunnelState(Mutex<SSHTunnels>);
 
 // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
 #[tauri::command]
-async fn containers_list() -> ListContainerResponse {
-    let podman = Podman::unix("/run/user/1000/podman/podman.sock");
-    podman
-        .containers()
-        .list(
-            &ContainerListOpts::builder()
-                .all(true)
-                .filter([ContainerListFilter::Pod("services".into())])
-                .build(),
-        )
-        .await
-        .map(|v| {
-            ListContainerResponse::Ok({
-                let mut data = v
-                    .into_iter()
-                    .filter(|c| !c.is_infra.unwrap_or(false))
-                    .map(|c| {
-                        ListContainerItem {
-                            id: {
-                                // println!("{:?}", c);
-                                c.id
-                            },
-                            name: c
-                                .names
-                                .as_ref()
-                                .and_then(|n| n.iter().next())
-                                .unwrap_or(&"unknown".to_string())
-                                .to_string(),
-                            state: c
-                                .state
-                                .and_then(|s| match s.as_str() {
-                                    "running" => Some(PodmanState::Running),
-                                    "exited" => Some(PodmanState::Exited),
-                                    "stopping" => Some(PodmanState::Stopping),
-                                    x => {
-                                        println!("{:?}", x);
-                                        None
-                                    }
-                                })
-                                .unwrap_or(PodmanState::Stopped),
-                            started_at: c.started_at,
-                            exited_at: c.exited_at,
-                        }
-                    })
-                    .collect::<Vec<_>>();
-                data.sort_by_key(|f| f.state);
-                data
-            })
-        })
-        .unwrap_or_else(|e| ListContainerResponse::Err(format!("{:?}", e)))
+async fn containers_list(
+    state: tauri::State<'_, ContainerState>,
+) -> Result<ListContainerResponse, ()> {
+    Ok(state.snapshot().await)
 }
 
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
 #[tauri::command]
-async fn set_container(id: String, state: PodmanState) -> () {
-    println!("setting container {} to {:?}", id, state);
-    let podman = Podman::unix("/run/user/1000/podman/podman.sock");
-    let container = podman.containers().get(id);
-    match state {
-        PodmanState::Running => container.start(None).await,
-        _ => container.stop(&Default::default()).await,
+async fn container_action(
+    id: String,
+    action: ContainerAction,
+    app: tauri::AppHandle,
+    containers: tauri::State<'_, ContainerState>,
+    notifier: tauri::State<'_, Notifier>,
+) -> Result<ActionResponse, ()> {
+    println!("running {:?} on container {}", action, id);
+    // the container stopping is expected, so nothing to notify about
+    if let Some(name) = containers.name(&id).await {
+        notifier.forget(&NotifyItem::Container(name)).await;
+    }
+    Ok(match podman::run_action(&app, &id, action).await {
+        Ok(()) => ActionResponse::Ok,
+        Err(e) => {
+            println!("unable to {:?} container {}: {}", action, id, e);
+            ActionResponse::Err(e)
+        }
+    })
+}
+
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
+#[tauri::command]
+async fn pod_action(
+    action: PodAction,
+    notifier: tauri::State<'_, Notifier>,
+) -> Result<ActionResponse, ()> {
+    println!("running {:?} on pod", action);
+    notifier.forget_containers().await;
+    Ok(match podman::run_pod_action(action).await {
+        Ok(()) => ActionResponse::Ok,
+        Err(e) => {
+            println!("unable to {:?} pod: {}", action, e);
+            ActionResponse::Err(e)
+        }
+    })
+}
+
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
+#[tauri::command]
+async fn stacks_list(state: tauri::State<'_, StackState>) -> Result<Vec<StackItem>, ()> {
+    Ok(state.list())
+}
+
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
+#[tauri::command]
+async fn stack_action(
+    name: String,
+    action: StackAction,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, StackState>,
+    notifier: tauri::State<'_, Notifier>,
+) -> Result<ActionResponse, ()> {
+    println!("running {:?} on stack {}", action, name);
+    notifier.forget_containers().await;
+    Ok(match state.run(&app, &name, action).await {
+        Ok(()) => ActionResponse::Ok,
+        Err(e) => {
+            println!("unable to {:?} stack {}: {}", action, name, e);
+            ActionResponse::Err(e)
+        }
+    })
+}
+
+#[tauri::command]
+async fn disk_usage() -> DiskUsageResponse {
+    match storage::disk_usage().await {
+        Ok(usage) => DiskUsageResponse::Ok(usage),
+        Err(e) => DiskUsageResponse::Err(e),
+    }
+}
+
+#[tauri::command]
+async fn prune_preview(target: PruneTarget) -> PreviewPruneResponse {
+    match storage::preview(target).await {
+        Ok(candidates) => PreviewPruneResponse::Ok(candidates),
+        Err(e) => PreviewPruneResponse::Err(e),
+    }
+}
+
+#[tauri::command]
+async fn prune(target: PruneTarget) -> PruneResponse {
+    println!("pruning {:?}", target);
+    match storage::prune(target).await {
+        Ok((removed, reclaimed)) => PruneResponse::Ok { removed, reclaimed },
+        Err(e) => {
+            println!("unable to prune {:?}: {}", target, e);
+            PruneResponse::Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+async fn container_exec(id: String, command: Vec<String>) -> ExecResponse {
+    exec::exec(&id, command).await
+}
+
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
+#[tauri::command]
+async fn terminal_open(
+    id: String,
+    command: Option<Vec<String>>,
+    rows: u16,
+    cols: u16,
+    app: tauri::AppHandle,
+    containers: tauri::State<'_, ContainerState>,
+    terminals: tauri::State<'_, TerminalState>,
+) -> Result<OpenTerminalResponse, ()> {
+    let command = match command {
+        Some(c) => c,
+        None => {
+            let name = containers
+                .containers
+                .lock()
+                .await
+                .as_ref()
+                .ok()
+                .and_then(|c| c.get(&id))
+                .map(|c| c.name.clone());
+            containers
+                .config
+                .lock()
+                .await
+                .iter()
+                .find(|c| Some(&c.name) == name.as_ref())
+                .and_then(|c| c.exec_command.clone())
+                .unwrap_or_default()
+        }
     };
+
+    Ok(match terminals.open(app, &id, command, rows, cols).await {
+        Ok(session) => OpenTerminalResponse::Ok(session),
+        Err(e) => OpenTerminalResponse::Err(e),
+    })
+}
+
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
+#[tauri::command]
+async fn terminal_write(
+    session: u32,
+    data: String,
+    terminals: tauri::State<'_, TerminalState>,
+) -> Result<(), ()> {
+    if let Err(e) = terminals.write(session, data).await {
+        println!("unable to write to terminal: {}", e);
+    }
+    Ok(())
+}
+
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
+#[tauri::command]
+async fn terminal_resize(
+    session: u32,
+    rows: u16,
+    cols: u16,
+    terminals: tauri::State<'_, TerminalState>,
+) -> Result<(), ()> {
+    if let Err(e) = terminals.resize(session, rows, cols).await {
+        println!("unable to resize terminal: {}", e);
+    }
+    Ok(())
+}
+
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
+#[tauri::command]
+async fn terminal_close(
+    session: u32,
+    terminals: tauri::State<'_, TerminalState>,
+) -> Result<(), ()> {
+    terminals.close(session).await;
+    Ok(())
 }
 
 /// note: we need to return a result here, Err corresponds to an exception so do not use it
@@ -181,6 +378,7 @@ async fn tunnels_list(state: tauri::State<'_, SSHTunnelState>) -> Result<ListTun
 async fn tunnels_toggle(
     id: String,
     state: TunnelState,
+    app: tauri::AppHandle,
     states: tauri::State<'_, SSHTunnelState>,
 ) -> Result<(), ()> {
     println!("setting tunnel {} to {:?}", id, state);
@@ -192,119 +390,146 @@ async fn tunnels_toggle(
     };
 
     match state {
-        TunnelState::On => tunnel.connect(),
+        TunnelState::On => tunnel.connect(&app),
         TunnelState::Off => tunnel.disconnect(),
     };
 
     Ok(())
 }
 
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
+#[tauri::command]
+async fn config_status(state: tauri::State<'_, ConfigState>) -> Result<ConfigStatus, ()> {
+    Ok(state.status().await)
+}
+
+#[tauri::command]
+async fn dependency_graph(app: tauri::AppHandle) -> DependencyGraph {
+    graph::graph(&app).await
+}
+
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
+#[tauri::command]
+async fn notifications_muted(notifier: tauri::State<'_, Notifier>) -> Result<Vec<NotifyItem>, ()> {
+    Ok(notifier.muted().await)
+}
+
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
+#[tauri::command]
+async fn notifications_mute(
+    item: NotifyItem,
+    muted: bool,
+    notifier: tauri::State<'_, Notifier>,
+) -> Result<(), ()> {
+    if let Err(e) = notifier.set_muted(item, muted).await {
+        println!("unable to save muted notifications: {}", e);
+    }
+    Ok(())
+}
+
 #[tauri::command]
 fn show(window: tauri::Window) {
     window.get_window("main").unwrap().show().unwrap();
 }
 
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
 #[tauri::command]
 async fn get_healthcheck(
-    state: tauri::State<'_, ServiceHealthCheckState>,
+    state: tauri::State<'_, HealthState>,
 ) -> Result<Vec<HealthcheckSection>, ()> {
-    Ok(
-        join_all(state.0.lock().await.iter().cloned().map(|c| async move {
-            HealthcheckSection {
-                name: c.name,
-                services: join_all(c.services.into_iter().map(|s| async move {
-                    let (up, db, elasticsearch) = match s.spring_healthcheck {
-                        Some(s) => {
-                            match reqwest::get(s).await.ok().filter(|r| {
-                                r.status() == StatusCode::OK
-                                    || r.status() == StatusCode::SERVICE_UNAVAILABLE
-                            }) {
-                                Some(r) => {
-                                    let req: SpringHealthCheck = r.json().await.unwrap();
-                                    (
-                                        req.components.ping.status.into(),
-                                        Some(req.components.db.status.into()),
-                                        Some(req.components.elasticsearch.status.into()),
-                                    )
-                                }
-                                None => (false, None, None),
-                            }
-                        }
-                        None => {
-                            let resp = reqwest::get(s.url.clone()).await;
-                            let up = resp.map(|r| !r.status().is_server_error()).unwrap_or(false);
-                            (up, None, None)
-                        }
-                    };
-                    ServiceHealthCheck {
-                        name: s.name,
-                        up,
-                        url: s.url.to_string(),
-                        db,
-                        elasticsearch,
-                    }
-                }))
-                .await,
-            }
-        }))
-        .await,
-    )
+    Ok(state.snapshot().await)
 }
 
-struct ServiceHealthCheckState(Mutex<Vec<ServiceSection>>);
+/// note: we need to return a result here, Err corresponds to an exception so do not use it
+#[tauri::command]
+async fn healthcheck_history(
+    section: String,
+    service: String,
+    since: u64,
+    state: tauri::State<'_, HealthState>,
+) -> Result<HealthHistoryResponse, ()> {
+    Ok(match state.history(&section, &service, since).await {
+        Ok(samples) => HealthHistoryResponse::Ok(samples),
+        Err(e) => HealthHistoryResponse::Err(e),
+    })
+}
 
 fn main() {
-    let config_dir = ProjectDirs::from("dev", "arlyon", "developer-dashboard")
-        .map(|d| d.config_dir().to_owned());
-    let config_file = config_dir.as_ref().map(|p| p.join("config.json"));
-
-    let config: Option<Config> = config_file
-        .as_ref()
-        .and_then(|f| File::open(f).ok())
-        .and_then(|f| match serde_json::from_reader(f) {
-            Ok(c) => Some(c),
+    let dirs = ProjectDirs::from("dev", "arlyon", "developer-dashboard");
+    let config_dir = dirs.as_ref().map(|d| d.config_dir().to_owned());
+    let data_dir = dirs.as_ref().map(|d| d.data_dir().to_owned());
+    let dir = config_dir.clone();
+
+    // the subcommands run before the app is built, so that they work without a display
+    let context = tauri::generate_context!();
+    if let Some(cli) = &context.config().tauri.cli {
+        attach_console();
+        let code = match get_matches(cli, context.package_info()) {
+            Ok(Matches {
+                subcommand: Some(cmd),
+                ..
+            }) => subcommand(*cmd, config_dir.as_deref()),
+            Ok(_) => None,
             Err(e) => {
-                println!("unable to load config, ignoring: {}", e);
-                None
+                println!("{}", e);
+                Some(1)
             }
-        });
+        };
+        if let Some(code) = code {
+            std::process::exit(code);
+        }
+    }
+
+    if let Some(dir) = &config_dir {
+        format::write_schema(dir);
+    }
+    let layers = config_dir
+        .as_deref()
+        .map(layers::read)
+        .unwrap_or_else(|| Ok(vec![]));
+    let config_file = layers
+        .as_ref()
+        .ok()
+        .and_then(|l| l.last())
+        .map(|l| l.file.display().to_string());
+    for layer in layers.iter().flatten() {
+        if layer.version < config::VERSION {
+            println!(
+                "{} is written for version {} of the config, run `migrate` to rewrite it for version {}",
+                layer.file.display(),
+                layer.version,
+                config::VERSION
+            );
+        }
+    }
+    let (config, problems) = match (config_dir.as_deref(), layers) {
+        (Some(dir), Ok(layers)) if !layers.is_empty() => {
+            let (config, problems) = validate::load(&layers, dir);
+            (config, problems.iter().map(ToString::to_string).collect())
+        }
+        (_, Err(e)) => (None, vec![e]),
+        _ => (None, vec![]),
+    };
+    for problem in &problems {
+        println!("leaving out what this affects in the config: {}", problem);
+    }
+    let status = ConfigStatus {
+        file: config_file,
+        problems,
+        previous: false,
+    };
+    let metrics = config.as_ref().and_then(|c| c.metrics);
+    let history_retention_days = config.as_ref().and_then(|c| c.history_retention_days);
 
     tauri::Builder::default()
-        .setup(|app| {
-            match app.get_cli_matches() {
-                Ok(Matches {
-                    subcommand: Some(cmd),
-                    ..
-                }) => {
-                    let SubcommandMatches { name, matches, .. } = *cmd;
-                    match name.as_str() {
-                        "fetch" => {
-                            let source_arg =
-                                matches.args.get("source").expect("validated by tauri");
-                            let source = source_arg.value.as_str().expect("validated by tauri");
-
-                            let res = block_on(fetch_config(
-                                source,
-                                &app.state::<SSHTunnelState>(),
-                                config_file,
-                            ));
-                            match res {
-                                Ok(_) => {}
-                                Err(e) => {
-                                    println!("{}", e);
-                                    std::process::exit(1);
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-                Err(e) => {
-                    println!("{}", e);
-                    std::process::exit(1);
-                }
-                _ => {}
-            };
+        .setup(move |app| {
+            tauri::async_runtime::spawn(podman::watch(app.handle()));
+            tauri::async_runtime::spawn(update::watch(app.handle()));
+            tauri::async_runtime::spawn(health::watch(app.handle()));
+            tauri::async_runtime::spawn(reload::watch(app.handle(), dir));
+            if let Some(address) = metrics {
+                tauri::async_runtime::spawn(metrics::serve(app.handle(), address));
+            }
 
             let win = app.get_window("main").unwrap();
 
@@ -316,51 +541,199 @@ fn main() {
 
             Ok(())
         })
+        .manage(ContainerState::new(
+            config
+                .as_ref()
+                .map(|c| c.containers.clone())
+                .unwrap_or_default(),
+            config.as_ref().and_then(|c| c.registry.clone()),
+        ))
+        .manage(TerminalState::default())
+        .manage(StackState::new(
+            config
+                .as_ref()
+                .map(|c| c.stacks.clone())
+                .unwrap_or_default(),
+            config_dir.clone(),
+        ))
         .manage(SSHTunnelState(Mutex::new(SSHTunnels(
             config
                 .as_ref()
                 .map(|c| c.tunnels.iter().cloned().map(Into::into).collect())
                 .unwrap_or_default(),
         ))))
-        .manage(ServiceHealthCheckState(Mutex::new(
+        .manage(HealthState::new(
             config.map(|c| c.services).unwrap_or_default(),
-        )))
+            History::open(data_dir.as_deref(), history_retention_days),
+            config_dir.clone(),
+        ))
+        .manage(Notifier::new(data_dir.as_deref()))
+        .manage(ConfigState::new(status))
         .invoke_handler(tauri::generate_handler![
             containers_list,
-            set_container,
+            container_action,
+            pod_action,
+            stacks_list,
+            stack_action,
+            disk_usage,
+            prune_preview,
+            prune,
+            container_exec,
+            terminal_open,
+            terminal_write,
+            terminal_resize,
+            terminal_close,
             tunnels_list,
             tunnels_toggle,
             get_healthcheck,
+            healthcheck_history,
+            dependency_graph,
+            notifications_muted,
+            notifications_mute,
+            config_status,
             show
         ])
-        .run(tauri::generate_context!())
+        .run(context)
         .expect("error while running tauri application");
 }
 
-async fn fetch_config(
-    source: &str,
-    config: &SSHTunnelState,
-    config_file: Option<PathBuf>,
-) -> Result<(), String> {
-    println!("fetching config from {}", source);
-    let body = reqwest::blocking::get(source).unwrap().text().unwrap();
-
-    let config_new: Config =
-        serde_json::from_str(&body).map_err(|e| format!("invalid config: {}", e))?;
-
-    let config_file = config_file
-        .and_then(|p| {
-            println!("saving config to {:?}", p);
-            std::fs::create_dir_all(&p.parent().expect("this is ok"));
-            File::create(p).ok()
-        })
-        .ok_or_else(|| format!("could not locate config path"))?;
+/// Runs a subcommand, returning the exit code unless the app should start afterwards.
+fn subcommand(cmd: SubcommandMatches, dir: Option<&Path>) -> Option<i32> {
+    let SubcommandMatches { name, matches, .. } = cmd;
+    let layers = || {
+        dir.ok_or_else(|| "could not locate config path".to_string())
+            .and_then(layers::read)
+    };
+    match name.as_str() {
+        "config" => {
+            let layers = layers();
+            if let Ok(layers) = &layers {
+                print!("{}", layers::describe(layers));
+            }
+            Some(check_config(layers, dir))
+        }
+        "validate" => {
+            let file = matches
+                .args
+                .get("file")
+                .and_then(|f| f.value.as_str())
+                .map(PathBuf::from);
+            // a file on its own is checked with the secrets next to it
+            let (layers, secrets) = match file {
+                Some(file) => (layers::read_file(&file), file.parent().map(Path::to_owned)),
+                None => (layers(), dir.map(Path::to_owned)),
+            };
+            let code = check_config(layers, secrets.as_deref());
+            if code == 0 {
+                println!("the config is valid");
+            }
+            Some(code)
+        }
+        "migrate" => {
+            let files = match matches.args.get("file").and_then(|f| f.value.as_str()) {
+                Some(file) => Ok(vec![PathBuf::from(file)]),
+                None => layers().map(|layers| layers.into_iter().map(|l| l.file).collect()),
+            };
+            Some(migrate_config(files))
+        }
+        "fetch" => {
+            let source_arg = matches.args.get("source").expect("validated by tauri");
+            let source = source_arg.value.as_str().expect("validated by tauri");
+            let headers = match matches.args.get("header").map(|h| &h.value) {
+                Some(serde_json::Value::Array(headers)) => headers
+                    .iter()
+                    .filter_map(|h| h.as_str().map(ToString::to_string))
+                    .collect(),
+                _ => vec![],
+            };
+            let dry_run = matches
+                .args
+                .get("dry-run")
+                .map_or(false, |d| d.value == serde_json::Value::Bool(true));
+
+            // the app then starts with the fetched config
+            match block_on(fetch::fetch(source, &headers, dry_run, dir)) {
+                Ok(_) if dry_run => Some(0),
+                Ok(_) => None,
+                Err(e) => {
+                    println!("{}", e);
+                    Some(1)
+                }
+            }
+        }
+        _ => None,
+    }
+}
 
-    serde_json::to_writer(config_file, &config_new);
+/// Lets a release build on windows, which has no console of its own, print to the one
+/// it was started from.
+#[cfg(windows)]
+fn attach_console() {
+    extern "system" {
+        fn AttachConsole(process: u32) -> i32;
+    }
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    // fails when there is no console to attach to, such as when started from the menu
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
 
-    let mut lock = config.0.lock().await;
-    lock.0
-        .extend(config_new.tunnels.into_iter().map(Into::into));
+#[cfg(not(windows))]
+fn attach_console() {}
+
+/// Prints the problems with a config for the subcommands, returning the exit code.
+fn check_config(layers: Result<Vec<layers::Layer>, String>, dir: Option<&Path>) -> i32 {
+    let problems = match (layers, dir) {
+        (Ok(layers), Some(dir)) => validate::load(&layers, dir)
+            .1
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        (Ok(_), None) => vec!["could not locate config path".to_string()],
+        (Err(e), _) => vec![e],
+    };
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    match problems.is_empty() {
+        true => 0,
+        false => 1,
+    }
+}
 
-    Ok(())
+fn migrate_config(files: Result<Vec<PathBuf>, String>) -> i32 {
+    let files = match files {
+        Ok(files) => files,
+        Err(e) => {
+            println!("{}", e);
+            return 1;
+        }
+    };
+    let mut code = 0;
+    for file in files {
+        match migrate::rewrite(&file) {
+            Ok(Some(version)) if version == config::VERSION => println!(
+                "wrote version {} into {}, the original is kept as {}.v{}",
+                version,
+                file.display(),
+                file.display(),
+                version
+            ),
+            Ok(Some(version)) => println!(
+                "rewrote {} from version {} to version {}, the original is kept as {}.v{}",
+                file.display(),
+                version,
+                config::VERSION,
+                file.display(),
+                version
+            ),
+            Ok(None) => println!("{} is up to date", file.display()),
+            Err(e) => {
+                println!("{}", e);
+                code = 1;
+            }
+        }
+    }
+    code
 }
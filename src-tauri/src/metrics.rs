@@ -0,0 +1,200 @@
+use std::{fmt::Write, net::SocketAddr, time::Duration};
+
+use commands::{ListContainerResponse, PodmanState};
+use tauri::{AppHandle, Manager};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{health::HealthState, podman::ContainerState, SSHTunnelState};
+
+/// The most we read of a request, which is plenty for a scrape
+const MAX_REQUEST: usize = 8 * 1024;
+
+/// How long a client gets to send its request, so that idle connections are dropped
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// serves the state of the dashboard on `/metrics` in the Prometheus text format
+pub async fn serve(app: AppHandle, address: SocketAddr) {
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("unable to serve metrics on {}: {}", address, e);
+            return;
+        }
+    };
+    println!("serving metrics on http://{}/metrics", address);
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = respond(&app, stream).await {
+                        println!("unable to serve metrics: {}", e);
+                    }
+                });
+            }
+            Err(e) => println!("unable to accept metrics connection: {}", e),
+        }
+    }
+}
+
+/// answers a single request, which is all a scraper sends before we close the connection
+async fn respond(app: &AppHandle, mut stream: TcpStream) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    let read = async {
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+            match stream.read(&mut buf).await? {
+                0 => break,
+                n => request.extend_from_slice(&buf[..n]),
+            }
+        }
+        Ok::<_, std::io::Error>(())
+    };
+    tokio::time::timeout(READ_TIMEOUT, read)
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "request timed out"))??;
+
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.split_whitespace();
+    // scrapers may add a query string, which there is no use for
+    let method = parts.next();
+    let path = parts
+        .next()
+        .map(|target| target.split_once('?').map_or(target, |(path, _)| path));
+    let (status, body) = match (method, path) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", render(app).await),
+        (Some("GET"), _) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\ncontent-type: text/plain; version=0.0.4\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// a family of metrics, written as its help and type followed by one line per series
+struct Family<'a> {
+    out: &'a mut String,
+    name: &'static str,
+}
+
+impl<'a> Family<'a> {
+    fn new(out: &'a mut String, name: &'static str, kind: &str, help: &str) -> Self {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        Self { out, name }
+    }
+
+    fn series(&mut self, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+        let labels = labels
+            .iter()
+            .map(|(k, v)| format!("{}=\"{}\"", k, escape(v)))
+            .collect::<Vec<_>>()
+            .join(",");
+        let _ = writeln!(self.out, "{}{{{}}} {}", self.name, labels, value);
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+async fn render(app: &AppHandle) -> String {
+    let mut out = String::new();
+
+    let sections = app.state::<HealthState>().snapshot().await;
+    let mut up = Family::new(
+        &mut out,
+        "dashboard_service_up",
+        "gauge",
+        "Whether the last healthcheck of the service passed.",
+    );
+    for section in &sections {
+        for service in &section.services {
+            let labels = [
+                ("section", section.name.as_str()),
+                ("service", &service.name),
+            ];
+            up.series(&labels, service.up as u8);
+        }
+    }
+
+    let mut latency = Family::new(
+        &mut out,
+        "dashboard_service_latency_seconds",
+        "gauge",
+        "How long the last healthcheck of the service took.",
+    );
+    for section in &sections {
+        for service in &section.services {
+            let labels = [
+                ("section", section.name.as_str()),
+                ("service", &service.name),
+            ];
+            latency.series(&labels, service.latency_ms as f64 / 1000.0);
+        }
+    }
+
+    let tunnels = {
+        let state = app.state::<SSHTunnelState>();
+        let mut tunnels = state.0.lock().await;
+        tunnels
+            .0
+            .iter_mut()
+            .map(|t| (t.tunnel.name.clone(), t.connected(), t.connects))
+            .collect::<Vec<_>>()
+    };
+    let mut connected = Family::new(
+        &mut out,
+        "dashboard_tunnel_connected",
+        "gauge",
+        "Whether the ssh tunnel is connected.",
+    );
+    for (name, is_connected, _) in &tunnels {
+        connected.series(&[("tunnel", name)], *is_connected as u8);
+    }
+
+    let mut reconnects = Family::new(
+        &mut out,
+        "dashboard_tunnel_reconnects_total",
+        "counter",
+        "How many times the ssh tunnel was connected again after the first time.",
+    );
+    for (name, _, connects) in &tunnels {
+        reconnects.series(&[("tunnel", name)], connects.saturating_sub(1));
+    }
+
+    let mut state = Family::new(
+        &mut out,
+        "dashboard_container_state",
+        "gauge",
+        "The state of the container, as a series for every state labelled with it, set to \
+         1 for the current one and 0 for the others.",
+    );
+    if let ListContainerResponse::Ok(containers) = app.state::<ContainerState>().snapshot().await {
+        for container in &containers {
+            for value in PodmanState::ALL {
+                let name = format!("{:?}", value).to_lowercase();
+                let current = container.state == value;
+                state.series(
+                    &[("container", &container.name), ("state", &name)],
+                    current as u8,
+                );
+            }
+        }
+    }
+
+    out
+}
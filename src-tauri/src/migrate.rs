@@ -0,0 +1,114 @@
+//! Brings config files written for an older version of the config up to the current
+//! one. Every file is migrated on its own when it is read, before the layers are
+//! merged, so that a fetched base and a local override don't need to be updated
+//! together. The `migrate` subcommand writes the result back.
+//!
+//! Changing the config in a way that older files no longer parse takes a new
+//! `config::VERSION`, a migration at the end of `MIGRATIONS` and the schema of the new
+//! version under `schemas/`, which the build script writes.
+
+use std::{fs, path::Path};
+
+use serde_json::{Map, Value};
+
+use crate::{config::VERSION, format, format::Format};
+
+/// The version of files without one, which were written before there were versions
+const UNVERSIONED: u32 = 1;
+
+/// The migrations in order, the first one taking a file from version 1 to 2
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[];
+
+/// Migrates a parsed file to the current version, returning the version it was
+/// written for.
+pub fn migrate(value: &mut Value) -> Result<u32, String> {
+    let config = match value.as_object_mut() {
+        Some(config) => config,
+        // not a config at all, which is reported when it is parsed
+        None => return Ok(VERSION),
+    };
+    let version = match config.get("version") {
+        None => UNVERSIONED,
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|v| *v >= UNVERSIONED)
+            .ok_or_else(|| format!("version: {} is not a config version", version))?,
+    };
+    if version > VERSION {
+        return Err(format!(
+            "version: the file is written for version {} of the config, this build only \
+             reads up to version {}",
+            version, VERSION
+        ));
+    }
+
+    for migration in &MIGRATIONS[(version - UNVERSIONED) as usize..] {
+        migration(config);
+    }
+    config.insert("version".to_string(), Value::from(VERSION));
+    Ok(version)
+}
+
+/// Rewrites a file for the current version, keeping the original next to it with the
+/// version it was written for appended to its name. Returns that version, or nothing
+/// when the file was already up to date.
+pub fn rewrite(file: &Path) -> Result<Option<u32>, String> {
+    let body = fs::read_to_string(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+    let (version, contents) = match rewritten(file, &body)? {
+        Some(rewritten) => rewritten,
+        None => return Ok(None),
+    };
+    let mut backup = file.to_owned().into_os_string();
+    backup.push(format!(".v{}", version));
+    fs::write(&backup, &body)
+        .and_then(|_| fs::write(file, contents))
+        .map_err(|e| format!("{}: {}", file.display(), e))?;
+    Ok(Some(version))
+}
+
+/// The contents of a file for the current version along with the version it was
+/// written for, or nothing when it is up to date. A file without a version gets one,
+/// even when there is nothing else to migrate.
+fn rewritten(file: &Path, body: &str) -> Result<Option<(u32, String)>, String> {
+    let mut value: Value = format::parse(file, body)?;
+    let versioned = value.get("version").is_some();
+    let version = migrate(&mut value).map_err(|e| format!("{}: {}", file.display(), e))?;
+    if version == VERSION && versioned {
+        return Ok(None);
+    }
+
+    let contents = Format::from_path(file)
+        .unwrap_or_else(|| Format::sniff(body))
+        .write(&value)?;
+    Ok(Some((version, contents)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::{json, Value};
+
+    use super::rewritten;
+    use crate::{config::VERSION, format::Format};
+
+    #[test]
+    fn an_unversioned_file_gets_its_version_written() {
+        let body = "tunnels: []\n";
+        let (version, contents) = rewritten(Path::new("config.yaml"), body)
+            .unwrap()
+            .expect("the file has no version");
+        assert_eq!(version, 1);
+        let value: Value = Format::Yaml.parse(&contents).unwrap();
+        assert_eq!(value, json!({"tunnels": [], "version": VERSION}));
+    }
+
+    #[test]
+    fn a_file_of_the_current_version_is_left_alone() {
+        let body = format!("version = {}\n", VERSION);
+        assert!(rewritten(Path::new("config.toml"), &body)
+            .unwrap()
+            .is_none());
+    }
+}
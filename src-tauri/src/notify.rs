@@ -0,0 +1,168 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use commands::NotifyItem;
+use tauri::{api::notification::Notification, async_runtime::Mutex, AppHandle, Manager};
+
+/// How long a new state has to hold before we notify about it, so that flapping items
+/// don't spam us
+const DEBOUNCE: Duration = Duration::from_secs(30);
+
+/// The last known state of an item
+struct Tracked {
+    up: bool,
+    /// bumped on every change, so that a pending notification can tell it is stale
+    generation: u64,
+    /// whether we told the user the item is down, and so owe them a recovery
+    announced_down: bool,
+}
+
+/// Turns state transitions into desktop notifications, minus the muted items
+pub struct Notifier {
+    muted: Mutex<HashSet<NotifyItem>>,
+    tracked: Mutex<HashMap<NotifyItem, Tracked>>,
+    /// where the muted items are saved
+    file: Option<PathBuf>,
+}
+
+impl Notifier {
+    pub fn new(dir: Option<&Path>) -> Self {
+        let file = dir.map(|d| d.join("muted.json"));
+        let muted = file
+            .as_ref()
+            .and_then(|f| File::open(f).ok())
+            .and_then(|f| match serde_json::from_reader(f) {
+                Ok(muted) => Some(muted),
+                Err(e) => {
+                    println!("unable to load muted notifications, ignoring: {}", e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            muted: Mutex::new(muted),
+            tracked: Mutex::new(HashMap::new()),
+            file,
+        }
+    }
+
+    pub async fn muted(&self) -> Vec<NotifyItem> {
+        self.muted.lock().await.iter().cloned().collect()
+    }
+
+    pub async fn set_muted(&self, item: NotifyItem, muted: bool) -> Result<(), String> {
+        let mut items = self.muted.lock().await;
+        match muted {
+            true => items.insert(item),
+            false => items.remove(&item),
+        };
+
+        let file = self.file.as_ref().ok_or("could not locate data dir")?;
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let file = File::create(file).map_err(|e| e.to_string())?;
+        serde_json::to_writer(file, &*items).map_err(|e| e.to_string())
+    }
+
+    /// drops what we know about an item, for changes the user asked for themselves
+    pub async fn forget(&self, item: &NotifyItem) {
+        self.tracked.lock().await.remove(item);
+    }
+
+    pub async fn forget_containers(&self) {
+        self.tracked
+            .lock()
+            .await
+            .retain(|item, _| !matches!(item, NotifyItem::Container(_)));
+    }
+
+    /// records the latest state of an item, notifying once a change has held for the
+    /// debounce period. the first state we see of an item is never a change.
+    pub async fn observe(
+        &self,
+        app: &AppHandle,
+        item: NotifyItem,
+        up: bool,
+        reason: Option<String>,
+    ) {
+        let generation = {
+            let mut tracked = self.tracked.lock().await;
+            let state = match tracked.get_mut(&item) {
+                Some(state) if state.up != up => state,
+                Some(_) => return,
+                None => {
+                    tracked.insert(
+                        item,
+                        Tracked {
+                            up,
+                            generation: 0,
+                            announced_down: false,
+                        },
+                    );
+                    return;
+                }
+            };
+            state.up = up;
+            state.generation += 1;
+            state.generation
+        };
+
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(DEBOUNCE).await;
+            app.state::<Notifier>()
+                .settle(&app, item, generation, reason)
+                .await;
+        });
+    }
+
+    async fn settle(
+        &self,
+        app: &AppHandle,
+        item: NotifyItem,
+        generation: u64,
+        reason: Option<String>,
+    ) {
+        let up = {
+            let mut tracked = self.tracked.lock().await;
+            let state = match tracked.get_mut(&item) {
+                Some(state) if state.generation == generation => state,
+                // forgotten or changed again in the meantime
+                _ => return,
+            };
+            // only a recovery from an outage we announced is worth telling about
+            if state.up != state.announced_down {
+                return;
+            }
+            state.announced_down = !state.up;
+            state.up
+        };
+
+        if self.muted.lock().await.contains(&item) {
+            return;
+        }
+
+        let notification = Notification::new(&app.config().tauri.bundle.identifier)
+            .title(title(&item, up))
+            .body(reason.unwrap_or_default());
+        if let Err(e) = notification.show() {
+            println!("unable to show notification: {}", e);
+        }
+    }
+}
+
+fn title(item: &NotifyItem, up: bool) -> String {
+    match (item, up) {
+        (NotifyItem::Service { name, .. }, true) => format!("{} is back up", name),
+        (NotifyItem::Service { name, .. }, false) => format!("{} is down", name),
+        (NotifyItem::Tunnel(name), true) => format!("Tunnel {} is back", name),
+        (NotifyItem::Tunnel(name), false) => format!("Tunnel {} dropped", name),
+        (NotifyItem::Container(name), true) => format!("Container {} is running again", name),
+        (NotifyItem::Container(name), false) => format!("Container {} died", name),
+    }
+}
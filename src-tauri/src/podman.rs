@@ -0,0 +1,431 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use commands::{
+    ContainerAction, ContainerEvent, ContainerHealth, Event, ListContainerItem,
+    ListContainerResponse, NotifyItem, PodAction, PodmanState, PublishedPort, PullEvent,
+};
+use futures::future::join_all;
+use futures::StreamExt;
+use podman_api::{
+    models::ListContainer,
+    opts::{ContainerDeleteOpts, ContainerListFilter, ContainerListOpts, EventsOpts, PullOpts},
+    Podman,
+};
+use reqwest::Url;
+use tauri::{async_runtime::Mutex, AppHandle, Manager};
+use tokio::sync::Notify;
+
+use crate::{config, health::HealthState, notify::Notifier};
+
+const PODMAN_SOCKET: &str = "/run/user/1000/podman/podman.sock";
+
+/// The pod whose containers are shown in the dashboard
+const POD: &str = "services";
+
+/// How long to wait before reconnecting to the engine after the event stream drops
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+pub fn podman() -> Podman {
+    Podman::unix(PODMAN_SOCKET)
+}
+
+/// The in-memory container model, kept up to date from the engine's event stream
+pub struct ContainerState {
+    pub containers: Mutex<Result<HashMap<String, ListContainerItem>, String>>,
+    /// per-container settings from the config
+    pub config: Mutex<Vec<config::Container>>,
+    /// the ids of the containers with a newer image in the registry
+    pub outdated: Mutex<HashSet<String>>,
+    /// the registry to check for image updates instead of the one in the image name
+    pub registry: Option<Url>,
+    /// woken when the containers are listed or one is created, so that they are checked
+    /// for updates without waiting for the next round
+    pub unchecked: Notify,
+}
+
+impl ContainerState {
+    pub fn new(config: Vec<config::Container>, registry: Option<Url>) -> Self {
+        Self {
+            containers: Mutex::new(Err("connecting to podman".to_string())),
+            config: Mutex::new(config),
+            outdated: Mutex::new(HashSet::new()),
+            registry,
+            unchecked: Notify::new(),
+        }
+    }
+
+    /// the name of a container we know about
+    pub async fn name(&self, id: &str) -> Option<String> {
+        match &*self.containers.lock().await {
+            Ok(containers) => containers.get(id).map(|c| c.name.clone()),
+            Err(_) => None,
+        }
+    }
+
+    pub async fn snapshot(&self) -> ListContainerResponse {
+        match &*self.containers.lock().await {
+            Ok(containers) => {
+                let mut data = containers.values().cloned().collect::<Vec<_>>();
+                data.sort_by_key(|f| f.state);
+                ListContainerResponse::Ok(data)
+            }
+            Err(e) => ListContainerResponse::Err(e.clone()),
+        }
+    }
+}
+
+/// lists the containers in the pod, optionally narrowed down by some extra filters
+async fn list(
+    podman: &Podman,
+    config: &[config::Container],
+    outdated: &HashSet<String>,
+    filters: impl IntoIterator<Item = ContainerListFilter>,
+) -> Result<Vec<ListContainerItem>, String> {
+    let containers = podman
+        .containers()
+        .list(
+            &ContainerListOpts::builder()
+                .all(true)
+                .filter(
+                    [ContainerListFilter::Pod(POD.into())]
+                        .into_iter()
+                        .chain(filters),
+                )
+                .build(),
+        )
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+
+    Ok(join_all(
+        containers
+            .into_iter()
+            .filter(|c| !c.is_infra.unwrap_or(false))
+            .map(|c| with_health(podman, to_item(c, config, outdated))),
+    )
+    .await)
+}
+
+/// the list endpoint doesn't include healthchecks, so we have to inspect each container
+async fn with_health(podman: &Podman, mut item: ListContainerItem) -> ListContainerItem {
+    if let Some(id) = &item.id {
+        item.health = podman
+            .containers()
+            .get(id)
+            .inspect()
+            .await
+            .ok()
+            .and_then(|i| i.state?.health?.status)
+            .and_then(|s| ContainerHealth::parse(&s));
+    }
+    item
+}
+
+fn to_item(
+    c: ListContainer,
+    config: &[config::Container],
+    outdated: &HashSet<String>,
+) -> ListContainerItem {
+    let name = c
+        .names
+        .as_ref()
+        .and_then(|n| n.iter().next())
+        .unwrap_or(&"unknown".to_string())
+        .to_string();
+
+    let ports = c
+        .ports
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|p| {
+            Some(PublishedPort {
+                host_port: p.host_port?,
+                container_port: p.container_port?,
+                protocol: p.protocol.unwrap_or_else(|| "tcp".to_string()),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let links = match config
+        .iter()
+        .find(|o| o.name == name)
+        .filter(|o| !o.links.is_empty())
+    {
+        Some(o) => o.links.iter().map(ToString::to_string).collect(),
+        None => ports
+            .iter()
+            .filter(|p| p.protocol == "tcp")
+            .map(|p| format!("http://localhost:{}", p.host_port))
+            .collect(),
+    };
+
+    ListContainerItem {
+        update_available: c.id.as_ref().map_or(false, |id| outdated.contains(id)),
+        id: c.id,
+        name,
+        state: c
+            .state
+            .as_deref()
+            .map(PodmanState::parse)
+            .unwrap_or(PodmanState::Unknown),
+        health: None,
+        started_at: c.started_at,
+        exited_at: c.exited_at,
+        ports,
+        links,
+        image: c.image.unwrap_or_default(),
+    }
+}
+
+/// keeps the container model in sync with the engine, pushing every change to the
+/// frontend, and reconnecting whenever the event stream drops
+pub async fn watch(app: AppHandle) {
+    let state = app.state::<ContainerState>();
+    let podman = podman();
+
+    loop {
+        // replay anything that happens while we are listing
+        let since = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let config = state.config.lock().await.clone();
+        let outdated = state.outdated.lock().await.clone();
+        let containers = list(&podman, &config, &outdated, []).await.map(|items| {
+            items
+                .into_iter()
+                .filter_map(|c| Some((c.id.clone()?, c)))
+                .collect::<HashMap<_, _>>()
+        });
+        let connected = containers.is_ok();
+        *state.containers.lock().await = containers;
+        emit(&app, ContainerEvent::Reset(state.snapshot().await));
+
+        if !connected {
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            continue;
+        }
+        state.unchecked.notify_one();
+
+        let opts = EventsOpts::builder()
+            .stream(true)
+            .since(since.to_string())
+            .filters([
+                ("type".to_string(), vec!["container".to_string()]),
+                ("pod".to_string(), vec![POD.to_string()]),
+            ])
+            .build();
+        let mut events = podman.events(&opts);
+
+        while let Some(event) = events.next().await {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    *state.containers.lock().await = Err(format!("{:?}", e));
+                    emit(&app, ContainerEvent::Reset(state.snapshot().await));
+                    break;
+                }
+            };
+
+            let name = event.actor.attributes.get("name").cloned();
+            match (event.action.as_str(), name) {
+                ("died", Some(name)) => {
+                    // containers that exit cleanly are done rather than dead
+                    if let Some(code) = event
+                        .actor
+                        .attributes
+                        .get("containerExitCode")
+                        .filter(|c| *c != "0")
+                    {
+                        let reason = format!("exited with code {}", code);
+                        app.state::<Notifier>()
+                            .observe(&app, NotifyItem::Container(name), false, Some(reason))
+                            .await;
+                    }
+                }
+                ("start", Some(name)) => {
+                    app.state::<Notifier>()
+                        .observe(&app, NotifyItem::Container(name), true, None)
+                        .await;
+                }
+                _ => {}
+            }
+
+            let id = event.actor.id;
+            let change = match event.action.as_str() {
+                "remove" => {
+                    if let Ok(containers) = &mut *state.containers.lock().await {
+                        containers.remove(&id);
+                    }
+                    ContainerEvent::Removed(id)
+                }
+                // exec sessions don't change the state of the container itself
+                action if action.starts_with("exec") => continue,
+                _ => {
+                    // not held while podman is asked, which would hold up the update checks
+                    let outdated = state.outdated.lock().await.clone();
+                    let item = list(
+                        &podman,
+                        &config,
+                        &outdated,
+                        [ContainerListFilter::Id(id.into())],
+                    )
+                    .await
+                    .map(|items| items.into_iter().next());
+                    match item {
+                        Ok(Some(item)) => {
+                            if let (Ok(containers), Some(id)) =
+                                (&mut *state.containers.lock().await, &item.id)
+                            {
+                                containers.insert(id.clone(), item.clone());
+                            }
+                            ContainerEvent::Updated(item)
+                        }
+                        // the container is already gone, the remove event will follow
+                        Ok(None) => continue,
+                        Err(e) => {
+                            println!("unable to refresh container: {}", e);
+                            continue;
+                        }
+                    }
+                }
+            };
+            emit(&app, change);
+            app.state::<HealthState>().wake();
+            if event.action == "create" {
+                state.unchecked.notify_one();
+            }
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// runs a lifecycle action against a container, returning the engine's error if it fails
+pub async fn run_action(app: &AppHandle, id: &str, action: ContainerAction) -> Result<(), String> {
+    let podman = podman();
+    let container = podman.containers().get(id);
+    match action {
+        ContainerAction::Start => container.start(None).await,
+        ContainerAction::Stop => container.stop(&Default::default()).await,
+        ContainerAction::Restart => container.restart().await,
+        ContainerAction::Pause => container.pause().await,
+        ContainerAction::Unpause => container.unpause().await,
+        ContainerAction::Kill => container.kill().await,
+        ContainerAction::Remove => {
+            container
+                .delete(&ContainerDeleteOpts::builder().force(true).build())
+                .await
+        }
+        ContainerAction::Recreate => return recreate(&podman, id, None).await,
+        ContainerAction::Update => return update(app, id).await,
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// runs a lifecycle action against every container in the pod, the individual
+/// containers report their progress through the event stream as usual
+pub async fn run_pod_action(action: PodAction) -> Result<(), String> {
+    let podman = podman();
+    let pod = podman.pods().get(POD);
+    let errs = match action {
+        PodAction::Start => pod.start().await.map(|r| r.errs),
+        PodAction::Stop => pod.stop().await.map(|r| r.errs),
+        PodAction::Restart => pod.restart().await.map(|r| r.errs),
+    }
+    .map_err(|e| e.to_string())?;
+
+    match errs.filter(|e| !e.is_empty()) {
+        Some(errs) => Err(errs.join("\n")),
+        None => Ok(()),
+    }
+}
+
+/// replaces a container with a fresh one built from the same configuration, optionally
+/// swapping out the image, and starts it again if it was running
+///
+/// the engine API has no way to express this, so we use `podman container clone` which
+/// carries over everything including mounts and pod membership
+pub async fn recreate(podman: &Podman, id: &str, image: Option<&str>) -> Result<(), String> {
+    let container = podman.containers().get(id);
+    let inspect = container.inspect().await.map_err(|e| e.to_string())?;
+    let name = inspect.name.ok_or("container has no name")?;
+    let running = inspect.state.and_then(|s| s.running).unwrap_or_default();
+
+    // the clone takes over the name, so the original has to step aside first
+    let old_name = format!("{}-recreating", name);
+    container
+        .stop(&Default::default())
+        .await
+        .or_else(|e| if running { Err(e) } else { Ok(()) })
+        .map_err(|e| e.to_string())?;
+    container
+        .rename(&old_name)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut command = tokio::process::Command::new("podman");
+    command
+        .args(["container", "clone", "--destroy"])
+        .arg(format!("--run={}", running))
+        .args([id, name.as_str()])
+        .args(image);
+
+    let res = match command.output().await {
+        Ok(output) if output.status.success() => return Ok(()),
+        Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        Err(e) => format!("unable to run podman: {}", e),
+    };
+
+    // put the original back the way we found it
+    container.rename(&name).await.ok();
+    if running {
+        container.start(None).await.ok();
+    }
+
+    Err(res)
+}
+
+/// pulls the latest version of the container's image, streaming the progress to the
+/// frontend, and recreates the container with it
+pub async fn update(app: &AppHandle, id: &str) -> Result<(), String> {
+    let podman = podman();
+    let image = podman
+        .containers()
+        .get(id)
+        .inspect()
+        .await
+        .map_err(|e| e.to_string())?
+        .image_name
+        .ok_or("container has no image")?;
+
+    let opts = PullOpts::builder().reference(&image).build();
+    let images = podman.images();
+    let mut pull = images.pull(&opts);
+    while let Some(report) = pull.next().await {
+        let report = report.map_err(|e| e.to_string())?;
+        if let Some(e) = report.error {
+            return Err(e);
+        }
+        if let Some(status) = report.stream {
+            let event = PullEvent {
+                id: id.to_string(),
+                status: status.trim().to_string(),
+            };
+            if let Err(e) = app.emit_all(PullEvent::name(), event) {
+                println!("unable to emit pull event: {}", e);
+            }
+        }
+    }
+
+    recreate(&podman, id, Some(&image)).await
+}
+
+pub fn emit(app: &AppHandle, event: ContainerEvent) {
+    if let Err(e) = app.emit_all(ContainerEvent::name(), event) {
+        println!("unable to emit container event: {}", e);
+    }
+}
@@ -0,0 +1,172 @@
+use reqwest::{header, Client, StatusCode, Url};
+use serde::Deserialize;
+
+/// The registry used for image names without one
+const DEFAULT_REGISTRY: &str = "docker.io";
+
+/// docker hub serves its API from a different host than the one in image names
+const DOCKER_HUB_API: &str = "registry-1.docker.io";
+
+/// The manifest types we accept, so that we get the same digest the engine stored on pull
+const MANIFEST_TYPES: &str = "application/vnd.oci.image.index.v1+json, \
+    application/vnd.docker.distribution.manifest.list.v2+json, \
+    application/vnd.oci.image.manifest.v1+json, \
+    application/vnd.docker.distribution.manifest.v2+json";
+
+/// An image reference split into the parts the registry API needs
+#[derive(Debug, PartialEq, Eq)]
+pub struct Reference {
+    pub registry: String,
+    pub repository: String,
+    pub tag: String,
+}
+
+impl Reference {
+    /// parses a tagged image name, returning None for images pinned to a digest since
+    /// those can never be out of date
+    pub fn parse(image: &str) -> Option<Self> {
+        if image.contains('@') {
+            return None;
+        }
+
+        let (registry, rest) = match image.split_once('/') {
+            Some((host, rest)) if host.contains(['.', ':']) || host == "localhost" => {
+                (host.to_string(), rest.to_string())
+            }
+            _ => (DEFAULT_REGISTRY.to_string(), image.to_string()),
+        };
+
+        let (repository, tag) = match rest.rsplit_once(':') {
+            Some((repository, tag)) if !tag.contains('/') => (repository, tag),
+            _ => (rest.as_str(), "latest"),
+        };
+
+        let repository = if registry == DEFAULT_REGISTRY && !repository.contains('/') {
+            format!("library/{}", repository)
+        } else {
+            repository.to_string()
+        };
+
+        Some(Self {
+            registry,
+            repository,
+            tag: tag.to_string(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+/// looks up the digest the registry currently serves for a reference, optionally asking
+/// a stand-in registry (a local mirror for example) instead of the one in the name
+pub async fn digest(
+    client: &Client,
+    reference: &Reference,
+    stand_in: Option<&Url>,
+) -> Result<String, String> {
+    let base = match stand_in {
+        Some(url) => url.as_str().trim_end_matches('/').to_string(),
+        None if reference.registry == DEFAULT_REGISTRY => format!("https://{}", DOCKER_HUB_API),
+        None => format!("https://{}", reference.registry),
+    };
+    let url = format!(
+        "{}/v2/{}/manifests/{}",
+        base, reference.repository, reference.tag
+    );
+
+    let head = |token: Option<String>| {
+        let mut req = client.head(&url).header(header::ACCEPT, MANIFEST_TYPES);
+        if let Some(token) = token {
+            req = req.bearer_auth(token);
+        }
+        req.send()
+    };
+
+    let mut resp = head(None).await.map_err(|e| e.to_string())?;
+    if resp.status() == StatusCode::UNAUTHORIZED {
+        let token = token(client, &resp).await?;
+        resp = head(Some(token)).await.map_err(|e| e.to_string())?;
+    }
+
+    if !resp.status().is_success() {
+        return Err(format!("registry responded with {}", resp.status()));
+    }
+
+    resp.headers()
+        .get("docker-content-digest")
+        .and_then(|d| d.to_str().ok())
+        .map(ToString::to_string)
+        .ok_or_else(|| "registry did not return a digest".to_string())
+}
+
+/// the `key=value` parameters of a challenge, where a value can be a quoted string with
+/// commas and escaped quotes in it, such as `scope="repository:x:pull,push"`
+fn params(challenge: &str) -> Vec<(String, String)> {
+    let mut params = vec![];
+    let mut chars = challenge.chars().peekable();
+    loop {
+        while chars.next_if(|c| *c == ',' || c.is_whitespace()).is_some() {}
+        let key =
+            std::iter::from_fn(|| chars.next_if(|c| *c != '=' && *c != ',')).collect::<String>();
+        if key.is_empty() && chars.peek().is_none() {
+            return params;
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            // a parameter without a value, which isn't one we use
+            continue;
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => value.extend(chars.next()),
+                    c => value.push(c),
+                }
+            }
+        } else {
+            value.extend(std::iter::from_fn(|| chars.next_if(|c| *c != ',')));
+        }
+        params.push((key.trim().to_string(), value.trim().to_string()));
+    }
+}
+
+/// fetches an anonymous token following the `WWW-Authenticate` challenge of a registry
+async fn token(client: &Client, resp: &reqwest::Response) -> Result<String, String> {
+    let challenge = resp
+        .headers()
+        .get(header::WWW_AUTHENTICATE)
+        .and_then(|c| c.to_str().ok())
+        .and_then(|c| c.strip_prefix("Bearer "))
+        .ok_or("registry requires authentication we don't support")?;
+
+    let params = params(challenge);
+    let realm = params
+        .iter()
+        .find(|(k, _)| k == "realm")
+        .map(|(_, v)| v.as_str())
+        .ok_or("registry challenge has no realm")?;
+    let query = params
+        .iter()
+        .filter(|(k, _)| k != "realm")
+        .collect::<Vec<_>>();
+
+    let resp: TokenResponse = client
+        .get(realm)
+        .query(&query)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    resp.token
+        .or(resp.access_token)
+        .ok_or_else(|| "registry returned no token".to_string())
+}
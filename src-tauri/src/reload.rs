@@ -0,0 +1,110 @@
+//! Applies changes to the config files while the app is running. Tunnels and services are
+//! reconciled with what is running, the other settings are only read at startup.
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use commands::{ConfigStatus, Event};
+use tauri::{async_runtime::Mutex, AppHandle, Manager};
+
+use crate::{
+    config::Config,
+    health, interpolate,
+    layers::{self, Layer},
+    validate, SSHTunnelState,
+};
+
+/// How often to look for changes to the config file
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Whether the config on disk is the one being used
+pub struct ConfigState(Mutex<ConfigStatus>);
+
+impl ConfigState {
+    pub fn new(status: ConfigStatus) -> Self {
+        Self(Mutex::new(status))
+    }
+
+    pub async fn status(&self) -> ConfigStatus {
+        self.0.lock().await.clone()
+    }
+}
+
+/// Polls the config files and the secret file, applying them whenever they change. A config that can't be read
+/// is reported to the frontend, and the previous one is kept running.
+pub async fn watch(app: AppHandle, dir: Option<PathBuf>) {
+    let dir = match dir {
+        Some(dir) => dir,
+        None => return,
+    };
+
+    let mut last = (layers::read(&dir), interpolate::secrets(&dir));
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let current = (layers::read(&dir), interpolate::secrets(&dir));
+        if current == last {
+            continue;
+        }
+        last = current;
+
+        // a deleted config keeps the running one, as if it was never changed
+        if matches!(&last.0, Ok(layers) if layers.is_empty()) {
+            continue;
+        }
+        println!("config changed, reloading");
+        update(&app, &dir, &last.0).await;
+    }
+}
+
+async fn update(app: &AppHandle, dir: &Path, layers: &Result<Vec<Layer>, String>) {
+    let file = match layers {
+        Ok(layers) => layers.last().map(|l| l.file.display().to_string()),
+        Err(_) => None,
+    };
+    // unlike at startup, a config with problems isn't applied at all, so that a
+    // mistake while editing doesn't take down what is running
+    let problems = match layers {
+        Ok(layers) => {
+            // secrets can be read from the keychain, which blocks
+            let (layers, dir) = (layers.clone(), dir.to_path_buf());
+            match tauri::async_runtime::spawn_blocking(move || validate::load(&layers, &dir)).await
+            {
+                Ok((Some(config), problems)) if problems.is_empty() => {
+                    apply(app, config).await;
+                    vec![]
+                }
+                Ok((_, problems)) => problems.iter().map(ToString::to_string).collect(),
+                Err(e) => vec![e.to_string()],
+            }
+        }
+        Err(e) => vec![e.clone()],
+    };
+    for problem in &problems {
+        println!(
+            "unable to reload config, keeping the previous one: {}",
+            problem
+        );
+    }
+
+    let status = ConfigStatus {
+        file,
+        previous: !problems.is_empty(),
+        problems,
+    };
+    *app.state::<ConfigState>().0.lock().await = status.clone();
+    if let Err(e) = app.emit_all(ConfigStatus::name(), status) {
+        println!("unable to emit config event: {}", e);
+    }
+}
+
+async fn apply(app: &AppHandle, config: Config) {
+    app.state::<SSHTunnelState>()
+        .0
+        .lock()
+        .await
+        .reconcile(app, config.tunnels)
+        .await;
+    health::apply(app, config.services).await;
+}
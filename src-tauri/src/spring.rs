@@ -1,17 +1,25 @@
-use podman_api::models::Health;
+use std::{collections::HashMap, time::Duration};
+
+use commands::{BuildInfo, ComponentStatus, HealthComponent};
+use reqwest::{RequestBuilder, Url};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 #[derive(Serialize, Deserialize)]
 pub struct SpringHealthCheck {
     pub status: HealthStatus,
-    pub components: SpringComponents,
+    #[serde(default)]
+    pub components: HashMap<String, SpringComponent>,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
 pub enum HealthStatus {
     UP,
     DOWN,
     OUT_OF_SERVICE,
+    /// spring's own unknown status, and any custom status an application defines
+    #[serde(other)]
+    UNKNOWN,
 }
 
 impl From<HealthStatus> for bool {
@@ -20,42 +28,175 @@ impl From<HealthStatus> for bool {
     }
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct SpringComponents {
-    pub db: ComponentStatus<DB>,
-    pub elasticsearch: ComponentStatus<ElasticSearch>,
-    #[serde(rename = "buildInfo")]
-    pub build_info: ComponentStatus<BuildInfo>,
-    pub ping: Ping,
+impl From<HealthStatus> for ComponentStatus {
+    fn from(s: HealthStatus) -> Self {
+        match s {
+            HealthStatus::UP => ComponentStatus::Up,
+            HealthStatus::DOWN => ComponentStatus::Down,
+            HealthStatus::OUT_OF_SERVICE => ComponentStatus::OutOfService,
+            HealthStatus::UNKNOWN => ComponentStatus::Unknown,
+        }
+    }
 }
 
+/// A health indicator, either a single one with some details or a composite of others
 #[derive(Serialize, Deserialize)]
-pub struct Ping {
+pub struct SpringComponent {
     pub status: HealthStatus,
+    #[serde(default)]
+    pub details: Map<String, Value>,
+    #[serde(default)]
+    pub components: HashMap<String, SpringComponent>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct ComponentStatus<T> {
-    pub status: HealthStatus,
-    pub details: T,
+impl SpringHealthCheck {
+    /// whether the service itself is up, which is what the ping indicator reports, so a
+    /// failing dependency doesn't take the service down with it
+    pub fn up(&self) -> bool {
+        self.components
+            .get("ping")
+            .map(|p| p.status)
+            .unwrap_or(self.status)
+            .into()
+    }
+
+    /// the version reported by the build info indicator, for services that expose it
+    /// through their health rather than the info endpoint
+    pub fn build_version(&self) -> Option<String> {
+        self.components
+            .get("buildInfo")?
+            .details
+            .get("build")?
+            .get("version")?
+            .as_str()
+            .map(ToString::to_string)
+    }
+
+    pub fn into_components(self) -> Vec<HealthComponent> {
+        into_components(self.components)
+    }
+}
+
+fn into_components(components: HashMap<String, SpringComponent>) -> Vec<HealthComponent> {
+    let mut components = components
+        .into_iter()
+        .map(|(name, c)| HealthComponent {
+            name,
+            status: c.status.into(),
+            details: c
+                .details
+                .into_iter()
+                .map(|(k, v)| match v {
+                    Value::String(s) => (k, s),
+                    v => (k, v.to_string()),
+                })
+                .collect(),
+            components: into_components(c.components),
+        })
+        .collect::<Vec<_>>();
+    components.sort_by(|a, b| a.name.cmp(&b.name));
+    components
+}
+
+/// The response of the actuator info endpoint, with the build and git contributors
+#[derive(Serialize, Deserialize, Default)]
+pub struct SpringInfo {
+    pub build: Option<SpringBuild>,
+    pub git: Option<SpringGit>,
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct DB {
-    pub database: Option<String>,
+pub struct SpringBuild {
+    pub version: Option<String>,
+    pub time: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct BuildInfo {
-    pub build: BuildInfoInner,
+pub struct SpringGit {
+    pub commit: Option<SpringCommit>,
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct BuildInfoInner {
-    pub version: String,
+pub struct SpringCommit {
+    pub id: Option<CommitId>,
 }
 
+/// The commit id, which is a plain string in the simple git info mode and both the
+/// abbreviated and full hash in the full mode
 #[derive(Serialize, Deserialize)]
-pub struct ElasticSearch {
-    pub cluster_name: Option<String>,
+#[serde(untagged)]
+pub enum CommitId {
+    Simple(String),
+    Full {
+        abbrev: Option<String>,
+        full: String,
+    },
+}
+
+impl SpringInfo {
+    /// the build info to show for a service, flagging the commit and version if they
+    /// aren't the ones the config expects
+    pub fn into_build_info(
+        self,
+        fallback_version: Option<String>,
+        expected_commit: Option<&str>,
+        expected_version: Option<&str>,
+    ) -> Option<BuildInfo> {
+        let commit = self.git.and_then(|g| g.commit?.id).map(|id| match id {
+            CommitId::Simple(id) => id,
+            CommitId::Full { full, .. } => full,
+        });
+        let (version, time) = match self.build {
+            Some(b) => (b.version.or(fallback_version), b.time),
+            None => (fallback_version, None),
+        };
+
+        if version.is_none() && commit.is_none() && time.is_none() {
+            return None;
+        }
+
+        let expected_commit = match (&commit, expected_commit) {
+            (Some(commit), Some(expected))
+                if !(commit.starts_with(expected) || expected.starts_with(commit.as_str())) =>
+            {
+                Some(expected.to_string())
+            }
+            _ => None,
+        };
+        let expected_version = match (&version, expected_version) {
+            (Some(version), Some(expected)) if version != expected => Some(expected.to_string()),
+            _ => None,
+        };
+
+        Some(BuildInfo {
+            version,
+            commit,
+            time,
+            expected_commit,
+            expected_version,
+        })
+    }
+}
+
+/// the info endpoint is usually next to the health endpoint
+pub fn info_url(health: &Url) -> Option<Url> {
+    let path = health.path().trim_end_matches('/').strip_suffix("health")?;
+    let mut url = health.clone();
+    url.set_path(&format!("{}info", path));
+    Some(url)
+}
+
+/// fetches the info endpoint, which is optional so any failure just means no info
+pub async fn info(request: RequestBuilder, timeout: Duration) -> SpringInfo {
+    let resp = request.timeout(timeout).send().await;
+    match resp.and_then(|r| r.error_for_status()) {
+        Ok(r) => {
+            let url = r.url().clone();
+            r.json().await.unwrap_or_else(|e| {
+                println!("invalid spring info from {}: {}", url, e);
+                SpringInfo::default()
+            })
+        }
+        Err(_) => SpringInfo::default(),
+    }
 }
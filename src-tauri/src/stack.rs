@@ -0,0 +1,117 @@
+use std::{path::PathBuf, process::Stdio};
+
+use commands::{Event, StackAction, StackEvent, StackItem};
+use tauri::{AppHandle, Manager};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::Command,
+};
+
+use crate::config::{Stack, StackKind};
+
+/// The stacks from the config, with their files resolved against the config directory
+pub struct StackState {
+    stacks: Vec<Stack>,
+    base: Option<PathBuf>,
+}
+
+impl StackState {
+    pub fn new(stacks: Vec<Stack>, base: Option<PathBuf>) -> Self {
+        Self { stacks, base }
+    }
+
+    pub fn list(&self) -> Vec<StackItem> {
+        self.stacks
+            .iter()
+            .map(|s| StackItem {
+                name: s.name.clone(),
+                file: self.path(s).display().to_string(),
+            })
+            .collect()
+    }
+
+    fn path(&self, stack: &Stack) -> PathBuf {
+        match &self.base {
+            Some(base) => base.join(&stack.file),
+            None => stack.file.clone(),
+        }
+    }
+
+    /// brings a stack up or down, streaming the engine's output to the frontend line by
+    /// line as each container is handled
+    pub async fn run(
+        &self,
+        app: &AppHandle,
+        name: &str,
+        action: StackAction,
+    ) -> Result<(), String> {
+        let stack = self
+            .stacks
+            .iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| format!("no stack named {}", name))?;
+        let file = self.path(stack);
+
+        let mut command = Command::new("podman");
+        match (kind(stack), action) {
+            (StackKind::Kube, StackAction::Up) => command.args(["kube", "play", "--replace"]),
+            (StackKind::Kube, StackAction::Down) => command.args(["kube", "down"]),
+            (StackKind::Compose, _) => command.args(["compose", "-f"]),
+        };
+        command.arg(&file);
+        match (kind(stack), action) {
+            (StackKind::Compose, StackAction::Up) => command.args(["up", "-d"]),
+            (StackKind::Compose, StackAction::Down) => command.arg("down"),
+            (StackKind::Kube, _) => &mut command,
+        };
+
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("unable to run podman: {}", e))?;
+
+        let stdout = child.stdout.take().expect("piped above");
+        let stderr = child.stderr.take().expect("piped above");
+        let (_, errors) = tokio::join!(forward(app, name, stdout), forward(app, name, stderr));
+
+        match child.wait().await {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(errors
+                .last()
+                .cloned()
+                .unwrap_or_else(|| format!("podman exited with {}", status))),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// emits each line of output as progress, returning the lines so errors can be reported
+async fn forward(app: &AppHandle, stack: &str, output: impl AsyncRead + Unpin) -> Vec<String> {
+    let mut lines = BufReader::new(output).lines();
+    let mut seen = vec![];
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = StackEvent {
+            stack: stack.to_string(),
+            line: line.clone(),
+        };
+        if let Err(e) = app.emit_all(StackEvent::name(), event) {
+            println!("unable to emit stack event: {}", e);
+        }
+        seen.push(line);
+    }
+    seen
+}
+
+/// the kind from the config, or a guess from the file name when it is missing
+fn kind(stack: &Stack) -> StackKind {
+    stack
+        .kind
+        .unwrap_or_else(|| match stack.file.file_name().and_then(|f| f.to_str()) {
+            Some(f) if f.contains("compose") => StackKind::Compose,
+            _ => StackKind::Kube,
+        })
+}
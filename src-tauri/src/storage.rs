@@ -0,0 +1,166 @@
+use std::cmp::Reverse;
+
+use commands::{DiskUsage, DiskUsageSummary, PruneCandidate, PruneTarget};
+use podman_api::models::{SystemDfContainerReport, SystemDfImageReport, SystemDfReport};
+
+use crate::podman::podman;
+
+/// how the engine marks the repository and tag of an untagged image
+const UNTAGGED: &str = "<none>";
+
+/// container states that a container prune leaves alone
+const ACTIVE_STATES: &[&str] = &["running", "paused", "restarting", "stopping"];
+
+fn untagged(image: &SystemDfImageReport) -> bool {
+    image.repository.as_deref().unwrap_or(UNTAGGED) == UNTAGGED
+        && image.tag.as_deref().unwrap_or(UNTAGGED) == UNTAGGED
+}
+
+fn stopped(container: &SystemDfContainerReport) -> bool {
+    !ACTIVE_STATES.contains(&container.status.as_deref().unwrap_or_default())
+}
+
+fn size(bytes: Option<i64>) -> u64 {
+    bytes.unwrap_or_default().max(0) as u64
+}
+
+async fn df() -> Result<SystemDfReport, String> {
+    podman().data_usage().await.map_err(|e| e.to_string())
+}
+
+/// summarizes the space used by the engine, counting each image by its unique size so
+/// that shared layers aren't counted more than once
+pub async fn disk_usage() -> Result<DiskUsage, String> {
+    let df = df().await?;
+    let images = df.images.unwrap_or_default();
+    let containers = df.containers.unwrap_or_default();
+    let volumes = df.volumes.unwrap_or_default();
+
+    let summarize = |images: Vec<&SystemDfImageReport>| DiskUsageSummary {
+        count: images.len(),
+        size: images.iter().map(|i| size(i.unique_size)).sum(),
+        reclaimable: images
+            .iter()
+            .filter(|i| i.containers.unwrap_or_default() == 0)
+            .map(|i| size(i.unique_size))
+            .sum(),
+    };
+    let (build_cache, tagged) = images.iter().partition(|i| untagged(i));
+
+    Ok(DiskUsage {
+        images: summarize(tagged),
+        build_cache: summarize(build_cache),
+        containers: DiskUsageSummary {
+            count: containers.len(),
+            size: containers.iter().map(|c| size(c.rw_size)).sum(),
+            reclaimable: containers
+                .iter()
+                .filter(|c| stopped(c))
+                .map(|c| size(c.rw_size))
+                .sum(),
+        },
+        volumes: DiskUsageSummary {
+            count: volumes.len(),
+            size: volumes.iter().map(|v| size(v.size)).sum(),
+            reclaimable: volumes.iter().map(|v| size(v.reclaimable_size)).sum(),
+        },
+    })
+}
+
+/// lists what a prune of the target would delete, using the same rules as the engine
+pub async fn preview(target: PruneTarget) -> Result<Vec<PruneCandidate>, String> {
+    let df = df().await?;
+    let mut candidates = match target {
+        PruneTarget::DanglingImages => df
+            .images
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|i| untagged(i) && i.containers.unwrap_or_default() == 0)
+            .map(|i| {
+                let id = i.image_id.unwrap_or_default();
+                PruneCandidate {
+                    name: id.chars().take(12).collect(),
+                    id,
+                    size: size(i.unique_size),
+                }
+            })
+            .collect::<Vec<_>>(),
+        PruneTarget::StoppedContainers => df
+            .containers
+            .unwrap_or_default()
+            .into_iter()
+            .filter(stopped)
+            .map(|c| PruneCandidate {
+                id: c.container_id.unwrap_or_default(),
+                name: c.names.unwrap_or_default(),
+                size: size(c.rw_size),
+            })
+            .collect(),
+        PruneTarget::UnusedVolumes => df
+            .volumes
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|v| v.links.unwrap_or_default() == 0)
+            .map(|v| {
+                let name = v.volume_name.unwrap_or_default();
+                PruneCandidate {
+                    id: name.clone(),
+                    name,
+                    size: size(v.size),
+                }
+            })
+            .collect(),
+    };
+    candidates.sort_by_key(|c| Reverse(c.size));
+    Ok(candidates)
+}
+
+/// deletes everything the target covers, returning how many items were removed and how
+/// many bytes that freed up
+pub async fn prune(target: PruneTarget) -> Result<(usize, u64), String> {
+    let podman = podman();
+    // (id, bytes, error) for each item in the engine's report
+    let reports: Vec<(Option<String>, u64, Option<String>)> = match target {
+        PruneTarget::DanglingImages => podman
+            .images()
+            .prune(&Default::default())
+            .await
+            .map_err(|e| e.to_string())?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|r| (r.id, r.size.unwrap_or_default(), r.err))
+            .collect(),
+        PruneTarget::StoppedContainers => podman
+            .containers()
+            .prune(&Default::default())
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|r| (r.id, size(r.size), r.err))
+            .collect(),
+        PruneTarget::UnusedVolumes => podman
+            .volumes()
+            .prune(&Default::default())
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|r| (r.id, r.size.unwrap_or_default(), r.err))
+            .collect(),
+    };
+
+    let errors = reports
+        .iter()
+        .filter_map(|(id, _, err)| {
+            let err = err.as_ref()?;
+            Some(format!("{}: {}", id.as_deref().unwrap_or("unknown"), err))
+        })
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    Ok((
+        reports.len(),
+        reports.iter().map(|(_, bytes, _)| bytes).sum(),
+    ))
+}
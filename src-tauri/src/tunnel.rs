@@ -4,7 +4,7 @@ use tokio::select;
 use tokio_util::sync::CancellationToken;
 
 /// Configuation for an SSH tunnel to a remote host
-#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct Tunnel {
     /// The name of the tunnel
     pub name: String,
@@ -23,8 +23,9 @@ pub struct Tunnel {
 }
 
 impl Tunnel {
-    /// opens a tunnel, and awaits until it closes
-    pub async fn open(&self, cancel: Option<CancellationToken>) -> () {
+    /// opens a tunnel, and awaits until it closes, returning whether it closed by itself
+    /// rather than being cancelled
+    pub async fn open(&self, cancel: Option<CancellationToken>) -> bool {
         let mut command = tokio::process::Command::new("ssh");
         let mut builder = command
             .arg("-L")
@@ -46,18 +47,20 @@ impl Tunnel {
         let mut child = builder.spawn().expect("must have libssh");
         let fut = child.wait();
 
-        match cancel {
+        let exited = match cancel {
             Some(cancel) => {
                 select! {
-                    _ = fut => { cancel.cancel() },
-                    _ = cancel.cancelled() => {},
+                    _ = fut => { cancel.cancel(); true },
+                    _ = cancel.cancelled() => false,
                 }
             }
             None => {
                 fut.await;
+                true
             }
         };
 
         println!("closed!");
+        exited
     }
 }
@@ -0,0 +1,121 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use commands::ContainerEvent;
+use podman_api::Podman;
+use reqwest::{Client, Url};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    podman::{emit, podman, ContainerState},
+    registry::{self, Reference},
+};
+
+/// How often to ask the registry for newer images
+const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long to wait on a registry, so that one that hangs doesn't hold up the others
+const REGISTRY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// periodically compares the image of every container with the registry, flagging the
+/// containers that run an outdated image. a round also runs as soon as the containers
+/// are first listed, and whenever one is created.
+pub async fn watch(app: AppHandle) {
+    let state = app.state::<ContainerState>();
+    let podman = podman();
+    let client = Client::builder()
+        .timeout(REGISTRY_TIMEOUT)
+        .build()
+        .expect("the client config is static");
+
+    loop {
+        let ids = match &*state.containers.lock().await {
+            Ok(containers) => containers.keys().cloned().collect::<Vec<_>>(),
+            Err(_) => vec![],
+        };
+
+        // containers often share an image, so only ask the registry once per round
+        let mut digests = HashMap::new();
+        let mut outdated = HashSet::new();
+        for id in ids {
+            match check(&podman, &client, state.registry.as_ref(), &id, &mut digests).await {
+                Ok(true) => {
+                    outdated.insert(id);
+                }
+                Ok(false) => {}
+                Err(e) => println!("unable to check container {} for updates: {}", id, e),
+            }
+        }
+
+        let changed = {
+            let mut current = state.outdated.lock().await;
+            let changed = current
+                .symmetric_difference(&outdated)
+                .map(|id| (id.clone(), outdated.contains(id)))
+                .collect::<Vec<_>>();
+            *current = outdated;
+            changed
+        };
+
+        for (id, update_available) in changed {
+            let item = match &mut *state.containers.lock().await {
+                Ok(containers) => containers.get_mut(&id).map(|item| {
+                    item.update_available = update_available;
+                    item.clone()
+                }),
+                Err(_) => None,
+            };
+            if let Some(item) = item {
+                emit(&app, ContainerEvent::Updated(item));
+            }
+        }
+
+        let _ = tokio::time::timeout(UPDATE_CHECK_INTERVAL, state.unchecked.notified()).await;
+    }
+}
+
+/// whether the registry serves a different digest than the one the container runs
+async fn check(
+    podman: &Podman,
+    client: &Client,
+    stand_in: Option<&Url>,
+    id: &str,
+    digests: &mut HashMap<String, Result<String, String>>,
+) -> Result<bool, String> {
+    let container = podman
+        .containers()
+        .get(id)
+        .inspect()
+        .await
+        .map_err(|e| e.to_string())?;
+    let (image_id, image_name) = match (container.image, container.image_name) {
+        (Some(id), Some(name)) => (id, name),
+        _ => return Ok(false),
+    };
+    let reference = match Reference::parse(&image_name) {
+        Some(r) => r,
+        None => return Ok(false),
+    };
+
+    let remote = match digests.get(&image_name) {
+        Some(digest) => digest.clone(),
+        None => {
+            let digest = registry::digest(client, &reference, stand_in).await;
+            digests.insert(image_name, digest.clone());
+            digest
+        }
+    }?;
+
+    let local = podman
+        .images()
+        .get(image_id)
+        .inspect()
+        .await
+        .map_err(|e| e.to_string())?
+        .repo_digests
+        .unwrap_or_default();
+
+    Ok(!local.iter().any(|d| d.ends_with(&remote)))
+}
@@ -0,0 +1,467 @@
+//! Checks the config for every problem at once, pointing at the file, line and column
+//! each one comes from. Entries are checked one by one, so that a mistake in one of
+//! them only leaves that one out.
+
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{
+    config::{Config, Container, Dependency, Service, ServiceSection, Stack},
+    interpolate,
+    layers::{self, Layer},
+    tunnel::Tunnel,
+};
+
+/// A step into the config, where entries of named lists are found by their name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Key(String),
+    Name(String),
+    Index(usize),
+}
+
+impl Step {
+    /// the step to an entry of a list, by name if it has one
+    pub fn entry(entry: &Value, index: usize) -> Self {
+        match entry.get("name").and_then(Value::as_str) {
+            Some(name) => Self::Name(name.to_string()),
+            None => Self::Index(index),
+        }
+    }
+}
+
+/// Something wrong with the config, and where it is
+#[derive(Debug)]
+pub struct Problem {
+    pub file: Option<PathBuf>,
+    /// the line and column, counting from one
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+            if let Some((line, column)) = self.position {
+                write!(f, "{}:{}:", line, column)?;
+            }
+            f.write_str(" ")?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Merges, resolves and checks the config, leaving out the entries with problems. The
+/// config is only missing when its top level has a problem. `dir` is where the secret
+/// file is looked up.
+pub fn load(layers: &[Layer], dir: &Path) -> (Option<Config>, Vec<Problem>) {
+    let mut checker = Checker {
+        layers,
+        problems: vec![],
+    };
+    for layer in layers {
+        checker.duplicates(layer, &layer.value, vec![]);
+    }
+
+    let mut merged = layers::merge(layers);
+    for (path, message) in interpolate::resolve(&mut merged, dir) {
+        checker.report(&path, message);
+    }
+
+    let config = checker.config(merged).map(|mut config| {
+        checker.ports(&mut config);
+        checker.dependencies(&mut config);
+        config
+    });
+
+    // trying again reports the same unknown fields, and the files are read in order
+    let mut seen = HashSet::new();
+    let mut problems = checker.problems;
+    problems.retain(|p| seen.insert(p.to_string()));
+    problems.sort_by(|a, b| (&a.file, a.position).cmp(&(&b.file, b.position)));
+    (config, problems)
+}
+
+struct Checker<'a> {
+    layers: &'a [Layer],
+    problems: Vec<Problem>,
+}
+
+impl Checker<'_> {
+    /// reports entries with the same name in a single file, which would otherwise be
+    /// merged into one without notice
+    fn duplicates(&mut self, layer: &Layer, value: &Value, path: Vec<Step>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    let mut path = path.clone();
+                    path.push(Step::Key(key.clone()));
+                    self.duplicates(layer, value, path);
+                }
+            }
+            Value::Array(entries) if layers::named(entries) => {
+                let mut seen = HashSet::new();
+                for (i, entry) in entries.iter().enumerate() {
+                    let mut path = path.clone();
+                    path.push(Step::entry(entry, i));
+                    let name = entry["name"].as_str().unwrap_or_default();
+                    if !seen.insert(name) {
+                        let message = "is listed more than once".to_string();
+                        // the later one is the duplicate
+                        let repeat = entries[..i]
+                            .iter()
+                            .filter(|e| e["name"].as_str() == Some(name))
+                            .count();
+                        self.problems.push(problem(layer, &path, message, repeat));
+                    }
+                    self.duplicates(layer, entry, path);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// reports a problem at a path of the merged config, in the file that set it
+    fn report(&mut self, path: &[Step], message: String) {
+        let problem = match origin(self.layers, path) {
+            Some(layer) => problem(layer, path, message, 0),
+            None => Problem {
+                file: None,
+                position: None,
+                message: format!("{}: {}", render(path), message),
+            },
+        };
+        self.problems.push(problem);
+    }
+
+    /// the config with the entries that have problems left out
+    fn config(&mut self, mut merged: Value) -> Option<Config> {
+        let tunnels = self.entries::<Tunnel>(&mut merged, "tunnels", &[]);
+        let containers = self.entries::<Container>(&mut merged, "containers", &[]);
+        let stacks = self.entries::<Stack>(&mut merged, "stacks", &[]);
+
+        // sections are checked without their services, which are checked one by one
+        let mut sections = vec![];
+        for (i, mut section) in take(&mut merged, "services").into_iter().enumerate() {
+            let path = vec![Step::Key("services".to_string()), Step::entry(&section, i)];
+            let services = self.entries::<Service>(&mut section, "services", &path);
+            if let Ok(mut section) = self.typed::<ServiceSection>(&section, &path) {
+                section.services = services;
+                sections.push(section);
+            }
+        }
+
+        let mut config = loop {
+            match self.typed::<Config>(&merged, &[]) {
+                Ok(config) => break config,
+                // leave out the setting with the problem and try again
+                Err(path) => {
+                    let removed = match (path.first(), merged.as_object_mut()) {
+                        (Some(Step::Key(key)), Some(object)) => object.remove(key),
+                        _ => None,
+                    };
+                    removed?;
+                }
+            }
+        };
+        config.tunnels = tunnels;
+        config.services = sections;
+        config.containers = containers;
+        config.stacks = stacks;
+        Some(config)
+    }
+
+    /// takes the entries of a list out of the value, and parses those without problems
+    fn entries<T: DeserializeOwned>(
+        &mut self,
+        value: &mut Value,
+        key: &str,
+        path: &[Step],
+    ) -> Vec<T> {
+        take(value, key)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, entry)| {
+                let mut path = path.to_vec();
+                path.push(Step::Key(key.to_string()));
+                path.push(Step::entry(&entry, i));
+                self.typed(&entry, &path).ok()
+            })
+            .collect()
+    }
+
+    /// parses a value, reporting the fields that aren't known along with any error, and
+    /// returning where the error is
+    fn typed<T: DeserializeOwned>(&mut self, value: &Value, path: &[Step]) -> Result<T, Vec<Step>> {
+        let mut unknown = vec![];
+        let mut callback = |p: serde_ignored::Path| unknown.push(ignored(&p));
+        let result: Result<T, _> = serde_path_to_error::deserialize(
+            serde_ignored::Deserializer::new(value.clone(), &mut callback),
+        );
+
+        for steps in unknown {
+            let mut full = path.to_vec();
+            full.extend(named(value, &steps));
+            self.report(&full, "is not a known field".to_string());
+        }
+
+        match result {
+            Ok(value) => Ok(value),
+            Err(e) => {
+                let steps = e
+                    .path()
+                    .iter()
+                    .filter_map(|segment| match segment {
+                        serde_path_to_error::Segment::Seq { index } => Some(Step::Index(*index)),
+                        serde_path_to_error::Segment::Map { key }
+                        | serde_path_to_error::Segment::Enum { variant: key } => {
+                            Some(Step::Key(key.clone()))
+                        }
+                        serde_path_to_error::Segment::Unknown => None,
+                    })
+                    .collect::<Vec<_>>();
+                let steps = named(value, &steps);
+                let mut full = path.to_vec();
+                full.extend(steps.iter().cloned());
+                self.report(&full, e.into_inner().to_string());
+                Err(steps)
+            }
+        }
+    }
+
+    /// leaves out the tunnels that use the local port of an earlier one
+    fn ports(&mut self, config: &mut Config) {
+        let mut ports = HashSet::new();
+        let mut duplicates = vec![];
+        config.tunnels.retain(|tunnel| {
+            let unique = ports.insert(tunnel.local_port);
+            if !unique {
+                duplicates.push(tunnel.clone());
+            }
+            unique
+        });
+        for tunnel in duplicates {
+            let path = [
+                Step::Key("tunnels".to_string()),
+                Step::Name(tunnel.name.clone()),
+                Step::Key("local_port".to_string()),
+            ];
+            let message = format!("{} is already used by another tunnel", tunnel.local_port);
+            self.report(&path, message);
+        }
+    }
+
+    /// leaves out the dependencies on tunnels, containers and services that aren't in the
+    /// config, which would otherwise never be up
+    fn dependencies(&mut self, config: &mut Config) {
+        let tunnels = config
+            .tunnels
+            .iter()
+            .map(|t| t.name.clone())
+            .collect::<HashSet<_>>();
+        let containers = config
+            .containers
+            .iter()
+            .map(|c| c.name.clone())
+            .collect::<HashSet<_>>();
+        let services = config
+            .services
+            .iter()
+            .flat_map(|s| s.services.iter().map(|s| s.name.clone()))
+            .collect::<HashSet<_>>();
+
+        let mut unknown = vec![];
+        for section in &mut config.services {
+            for service in &mut section.services {
+                let mut index = 0;
+                service.depends_on.retain(|dependency| {
+                    let (known, kind) = match dependency {
+                        Dependency::Tunnel(name) => (tunnels.contains(name), "tunnel"),
+                        Dependency::Container(name) => (containers.contains(name), "container"),
+                        Dependency::Service(name) => (services.contains(name), "service"),
+                    };
+                    if !known {
+                        let path = vec![
+                            Step::Key("services".to_string()),
+                            Step::Name(section.name.clone()),
+                            Step::Key("services".to_string()),
+                            Step::Name(service.name.clone()),
+                            Step::Key("depends_on".to_string()),
+                            Step::Index(index),
+                        ];
+                        unknown.push((path, format!("is not a {} in the config", kind)));
+                    }
+                    index += 1;
+                    known
+                });
+            }
+        }
+        for (path, message) in unknown {
+            self.report(&path, message);
+        }
+    }
+}
+
+/// Takes a list out of an object, leaving an empty one.
+fn take(value: &mut Value, key: &str) -> Vec<Value> {
+    let entries = value
+        .as_object_mut()
+        .and_then(|o| o.insert(key.to_string(), Value::Array(vec![])));
+    match entries {
+        Some(Value::Array(entries)) => entries,
+        // not a list, which is reported when parsing the object it is in
+        Some(other) => {
+            value[key] = other;
+            vec![]
+        }
+        None => vec![],
+    }
+}
+
+fn ignored(path: &serde_ignored::Path) -> Vec<Step> {
+    let (parent, step) = match path {
+        serde_ignored::Path::Root => return vec![],
+        serde_ignored::Path::Seq { parent, index } => (parent, Some(Step::Index(*index))),
+        serde_ignored::Path::Map { parent, key } => (parent, Some(Step::Key(key.clone()))),
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => (parent, None),
+    };
+    let mut steps = ignored(parent);
+    steps.extend(step);
+    steps
+}
+
+/// Replaces the indices into named lists with the names, which are the same in every
+/// file while the indices aren't.
+fn named(value: &Value, steps: &[Step]) -> Vec<Step> {
+    let mut current = Some(value);
+    steps
+        .iter()
+        .map(|step| {
+            let step = match (step, current) {
+                (Step::Index(i), Some(Value::Array(entries))) => entries
+                    .get(*i)
+                    .map_or(step.clone(), |entry| Step::entry(entry, *i)),
+                _ => step.clone(),
+            };
+            current = current.and_then(|c| lookup(c, std::slice::from_ref(&step)));
+            step
+        })
+        .collect()
+}
+
+fn lookup<'a>(value: &'a Value, steps: &[Step]) -> Option<&'a Value> {
+    steps.iter().try_fold(value, |value, step| match step {
+        Step::Key(key) => value.get(key),
+        Step::Index(i) => value.get(i),
+        Step::Name(name) => value
+            .as_array()?
+            .iter()
+            .find(|e| e["name"].as_str() == Some(name)),
+    })
+}
+
+/// The last layer that sets the value at the path, or the closest value around it.
+fn origin<'a>(layers: &'a [Layer], path: &[Step]) -> Option<&'a Layer> {
+    (1..=path.len()).rev().find_map(|len| {
+        layers
+            .iter()
+            .rev()
+            .find(|l| lookup(&l.value, &path[..len]).is_some())
+    })
+}
+
+fn problem(layer: &Layer, path: &[Step], message: String, repeat: usize) -> Problem {
+    let position = fs::read_to_string(&layer.file)
+        .ok()
+        .and_then(|text| position(&text, path, repeat));
+    Problem {
+        file: Some(layer.file.clone()),
+        position,
+        message: format!("{}: {}", render(path), message),
+    }
+}
+
+/// Finds a path in the text of a file by looking for each key and name in turn, which
+/// works for all of the formats without keeping track of where values were parsed from.
+/// The last step is skipped over `repeat` times. This falls back to the closest step that
+/// was found.
+fn position(text: &str, path: &[Step], repeat: usize) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    let mut found = None;
+    for (i, step) in path.iter().enumerate() {
+        let token = match step {
+            Step::Key(token) | Step::Name(token) => token,
+            Step::Index(_) => continue,
+        };
+        let skip = if i == path.len() - 1 { repeat } else { 0 };
+        let value = matches!(step, Step::Name(_));
+        let mut start = find(text, offset, token, value);
+        for _ in 0..skip {
+            start = start.and_then(|s| find(text, s + token.len(), token, value));
+        }
+        match start {
+            Some(start) => {
+                offset = start + token.len();
+                found = Some(start);
+            }
+            None => break,
+        }
+    }
+
+    let before = &text[..found?];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Some((
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    ))
+}
+
+/// Finds a whole word, so that `port` doesn't match in `local_port`. A `value` has to
+/// be the whole value, so that `db` doesn't match in `db.internal` either.
+fn find(text: &str, from: usize, token: &str, value: bool) -> Option<usize> {
+    let word = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let starts = |c: char| c.is_whitespace() || "\"':=[{,".contains(c);
+    let ends = |c: char| c.is_whitespace() || "\"',}]#".contains(c);
+    let mut start = from;
+    while let Some(i) = text[start..].find(token) {
+        let i = start + i;
+        let end = i + token.len();
+        let before = text[..i].chars().next_back();
+        let after = text[end..].chars().next();
+        let whole = match value {
+            true => before.map_or(true, starts) && after.map_or(true, ends),
+            false => !before.map_or(false, word) && !after.map_or(false, word),
+        };
+        if whole {
+            return Some(i);
+        }
+        start = end;
+    }
+    None
+}
+
+/// A path as it would be written in code, such as `tunnels[db].local_port`.
+fn render(path: &[Step]) -> String {
+    let mut out = String::new();
+    for step in path {
+        match step {
+            Step::Key(key) if out.is_empty() => out.push_str(key),
+            Step::Key(key) => {
+                out.push('.');
+                out.push_str(key)
+            }
+            Step::Name(name) => out.push_str(&format!("[{}]", name)),
+            Step::Index(i) => out.push_str(&format!("[{}]", i)),
+        }
+    }
+    out
+}
@@ -1,13 +1,20 @@
-use std::sync::Arc;
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
 
-use chrono::{Duration, Local};
+use chrono::{DateTime, Duration, Local};
 use chrono_humanize::HumanTime;
 use commands::{
-    Command, GetHealthCheck, ListContainerResponse, ListContainers, ListTunnelResponse,
-    ListTunnels, PodmanState, ServiceHealthCheck, SetContainerStatus, ToggleTunnels, TunnelState,
-    TunnelStatus,
+    ActionResponse, BuildInfo, CloseTerminal, Command, ComponentStatus, ConfigStatus,
+    ContainerAction, ContainerEvent, ContainerHealth, DependencyGraph, DiskUsage,
+    DiskUsageResponse, DiskUsageSummary, Event, GetConfigStatus, GetDependencyGraph, GetDiskUsage,
+    GetHealthCheck, GetHealthHistory, GraphNode, HealthComponent, HealthHistoryResponse,
+    HealthSample, HealthcheckEvent, ListContainerResponse, ListContainers, ListMuted, ListStacks,
+    ListTunnelResponse, ListTunnels, NodeKind, NotifyItem, OpenTerminal, OpenTerminalResponse,
+    PodAction, PodmanState, PreviewPrune, PreviewPruneResponse, Prune, PruneCandidate,
+    PruneResponse, PruneTarget, PublishedPort, PullEvent, ResizeTerminal, RunContainerAction,
+    RunPodAction, RunStackAction, ServiceHealthCheck, SetMuted, StackAction, StackEvent, StackItem,
+    TerminalEvent, ToggleTunnels, TunnelState, TunnelStatus, WriteTerminal,
 };
-use gloo_timers::callback::Timeout;
+use gloo_timers::callback::{Interval, Timeout};
 use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::to_value;
 use wasm_bindgen::prelude::*;
@@ -96,10 +103,14 @@ pub fn app() -> Html {
                         <span style="width: max-content">{"Clickup Tasks"}</span>
                     </a>
                 </div>
+                <ConfigBanner />
                 <HealthcheckSection />
+                <DependencySection />
                 <div />
                 <SSHSection />
                 <PodmanSection />
+                <StacksSection />
+                <StorageSection />
             </div>
         </main>
     }
@@ -123,34 +134,161 @@ fn section(props: &SectionProps) -> Html {
     }
 }
 
+#[derive(Properties, PartialEq)]
+pub struct ConfigBannerProps {}
+
+/// The problems with the config, shown until they are fixed
+#[function_component(ConfigBanner)]
+fn config_banner(_props: &ConfigBannerProps) -> Html {
+    let status = use_state(ConfigStatus::default);
+
+    {
+        let status = status.clone();
+        use_effect_with_deps(
+            move |_| {
+                let subscription = {
+                    let status = status.clone();
+                    ConfigStatus::subscribe(move |s| status.set(s))
+                };
+                spawn_local(async move { status.set(GetConfigStatus {}.invoke().await) });
+                move || drop(subscription)
+            },
+            (),
+        );
+    }
+
+    if status.problems.is_empty() {
+        return html! {};
+    }
+    let title = match status.previous {
+        true => "The config has problems, the previous one is still in use",
+        false => "The config has problems, what they affect was left out",
+    };
+    html! {
+        <div class="toast config-error">
+            <strong>{title}</strong>
+            <ul>
+                {status.problems.iter().map(|p| html! {<li>{p}</li>}).collect::<Html>()}
+            </ul>
+        </div>
+    }
+}
+
 #[derive(Properties, PartialEq)]
 pub struct HealthcheckSectionProps {}
 
+/// The healthcheck results, kept in sync with the events pushed by the backend
+#[derive(Default)]
+struct Healthchecks {
+    sections: Vec<commands::HealthcheckSection>,
+    /// how many snapshots were requested and haven't arrived yet
+    fetching: usize,
+    /// the updates since the oldest of those was requested, which may be newer than
+    /// the snapshot and are applied on top of it
+    pending: Vec<HealthcheckEvent>,
+}
+
+enum HealthcheckAction {
+    Fetching,
+    Reset(Vec<commands::HealthcheckSection>),
+    Updated(Box<HealthcheckEvent>),
+}
+
+impl Healthchecks {
+    fn apply(&mut self, event: HealthcheckEvent) {
+        let HealthcheckEvent { section, service } = event;
+        let index = match self.sections.iter().position(|s| s.name == section) {
+            Some(i) => i,
+            None => {
+                self.sections.push(commands::HealthcheckSection {
+                    name: section,
+                    services: vec![],
+                });
+                self.sections.len() - 1
+            }
+        };
+        let services = &mut self.sections[index].services;
+        match services.iter_mut().find(|s| s.name == service.name) {
+            Some(s) => *s = service,
+            None => services.push(service),
+        }
+    }
+}
+
+impl Reducible for Healthchecks {
+    type Action = HealthcheckAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        let mut next = Healthchecks {
+            sections: self.sections.clone(),
+            fetching: self.fetching,
+            pending: self.pending.clone(),
+        };
+        match action {
+            HealthcheckAction::Fetching => next.fetching += 1,
+            HealthcheckAction::Reset(sections) => {
+                next.sections = sections;
+                next.fetching = next.fetching.saturating_sub(1);
+                let pending = match next.fetching {
+                    0 => std::mem::take(&mut next.pending),
+                    _ => next.pending.clone(),
+                };
+                for event in pending {
+                    next.apply(event);
+                }
+            }
+            HealthcheckAction::Updated(event) => {
+                if next.fetching > 0 {
+                    next.pending.push((*event).clone());
+                }
+                next.apply(*event);
+            }
+        }
+        Rc::new(next)
+    }
+}
+
 #[function_component(HealthcheckSection)]
-fn healthcheck_section(props: &HealthcheckSectionProps) -> Html {
-    let greet_msg = use_state(|| vec![]);
+fn healthcheck_section(_props: &HealthcheckSectionProps) -> Html {
+    let healthchecks = use_reducer(Healthchecks::default);
 
     {
-        let greet_msg = greet_msg.clone();
-        let timeout = Timeout::new(1_000, move || {
-            spawn_local(async move {
-                let resp = GetHealthCheck {}.invoke().await;
-                greet_msg.set(resp);
-            });
-        });
-
-        timeout.forget();
+        let healthchecks = healthchecks.clone();
+        use_effect_with_deps(
+            move |_| {
+                let subscription = {
+                    let healthchecks = healthchecks.clone();
+                    HealthcheckEvent::subscribe(move |e| {
+                        healthchecks.dispatch(HealthcheckAction::Updated(Box::new(e)))
+                    })
+                };
+                let fetch = move || {
+                    let healthchecks = healthchecks.clone();
+                    healthchecks.dispatch(HealthcheckAction::Fetching);
+                    spawn_local(async move {
+                        let resp = GetHealthCheck {}.invoke().await;
+                        healthchecks.dispatch(HealthcheckAction::Reset(resp));
+                    });
+                };
+                fetch();
+                // services may have been added or removed
+                let reloaded = ConfigStatus::subscribe(move |_| fetch());
+                move || drop((subscription, reloaded))
+            },
+            (),
+        );
     }
 
-    greet_msg
+    healthchecks
+        .sections
         .iter()
         .cloned()
         .map(|mut hc| {
             hc.services.sort_by_key(|s| !s.up); // online services first
             html! {
-                <Section title={hc.name}>
+                <Section title={hc.name.clone()}>
                 <div style="display: grid; width: 100%; gap: 1em">
-                    {hc.services.iter().cloned().map(|s| html!{<HealthcheckEntry hc={s} />}).collect::<Html>()}
+                    {hc.services.iter().cloned().map(|s| html!{<HealthcheckEntry section={hc.name.clone()} hc={s} />}).collect::<Html>()}
                 </div>
                     </Section>
             }
@@ -158,35 +296,320 @@ fn healthcheck_section(props: &HealthcheckSectionProps) -> Html {
         .collect::<Html>()
 }
 
+#[derive(Properties, PartialEq)]
+pub struct DependencySectionProps {}
+
+/// The size of a node in the dependency graph, and the space around it
+const NODE_WIDTH: i32 = 160;
+const NODE_HEIGHT: i32 = 28;
+const NODE_GAP: i32 = 16;
+const COLUMN_GAP: i32 = 60;
+
+/// The services and everything they depend on, with dependencies to the left of their
+/// dependents
+#[function_component(DependencySection)]
+fn dependency_section(_props: &DependencySectionProps) -> Html {
+    let graph = use_state(DependencyGraph::default);
+
+    {
+        let graph = graph.clone();
+        use_effect_with_deps(
+            move |_| {
+                let fetch = move || {
+                    let graph = graph.clone();
+                    spawn_local(async move { graph.set(GetDependencyGraph {}.invoke().await) });
+                };
+                fetch();
+                // any healthcheck result or a new config may change the graph
+                let subscription = {
+                    let fetch = fetch.clone();
+                    HealthcheckEvent::subscribe(move |_| fetch())
+                };
+                let reloaded = ConfigStatus::subscribe(move |_| fetch());
+                move || drop((subscription, reloaded))
+            },
+            (),
+        );
+    }
+
+    if graph.edges.is_empty() {
+        return html! {};
+    }
+
+    let positions = layout(&graph);
+    let position = |id: &str| positions.get(id).copied().unwrap_or_default();
+    let columns = positions
+        .values()
+        .map(|(c, _)| c + 1)
+        .max()
+        .unwrap_or_default();
+    let rows = positions
+        .values()
+        .map(|(_, r)| r + 1)
+        .max()
+        .unwrap_or_default();
+    let width = columns * (NODE_WIDTH + COLUMN_GAP) - COLUMN_GAP;
+    let height = rows * (NODE_HEIGHT + NODE_GAP) - NODE_GAP;
+
+    let edges = graph
+        .edges
+        .iter()
+        .map(|(dependent, dependency)| {
+            let (from_column, from_row) = position(dependent);
+            let (to_column, to_row) = position(dependency);
+            html! {
+                <line
+                    x1={(from_column * (NODE_WIDTH + COLUMN_GAP)).to_string()}
+                    y1={(from_row * (NODE_HEIGHT + NODE_GAP) + NODE_HEIGHT / 2).to_string()}
+                    x2={(to_column * (NODE_WIDTH + COLUMN_GAP) + NODE_WIDTH).to_string()}
+                    y2={(to_row * (NODE_HEIGHT + NODE_GAP) + NODE_HEIGHT / 2).to_string()}
+                />
+            }
+        })
+        .collect::<Html>();
+
+    let nodes = graph
+        .nodes
+        .iter()
+        .map(|node| {
+            let (column, row) = position(&node.id);
+            let class = match (node.up, &node.blocked_by) {
+                (Some(true), _) => "node up",
+                (Some(false), Some(_)) => "node blocked",
+                (Some(false), None) => "node down",
+                (None, _) => "node",
+            };
+            html! {
+                <g {class} transform={format!(
+                    "translate({}, {})",
+                    column * (NODE_WIDTH + COLUMN_GAP),
+                    row * (NODE_HEIGHT + NODE_GAP)
+                )}>
+                    <title>{node_title(node)}</title>
+                    <rect width={NODE_WIDTH.to_string()} height={NODE_HEIGHT.to_string()} rx="6" />
+                    <text x="10" y={(NODE_HEIGHT / 2).to_string()}>{&node.name}</text>
+                </g>
+            }
+        })
+        .collect::<Html>();
+
+    html! {
+        <Section title="Dependencies">
+            <svg class="dependency-graph" viewBox={format!("-1 -1 {} {}", width + 2, height + 2)} width={(width + 2).to_string()}>
+                {edges}
+                {nodes}
+            </svg>
+        </Section>
+    }
+}
+
+fn node_title(node: &GraphNode) -> String {
+    let kind = match node.kind {
+        NodeKind::Service => "service",
+        NodeKind::Tunnel => "tunnel",
+        NodeKind::Container => "container",
+    };
+    match (node.up, &node.blocked_by) {
+        (Some(true), _) => format!("{} {} is up", kind, node.name),
+        (Some(false), Some(blocked_by)) => {
+            format!("{} {} is blocked by {}", kind, node.name, blocked_by)
+        }
+        (Some(false), None) => format!("{} {} is down", kind, node.name),
+        (None, _) => format!("{} {} hasn't been checked yet", kind, node.name),
+    }
+}
+
+/// the column and row of every node, putting each node one column after the deepest of
+/// its dependencies
+fn layout(graph: &DependencyGraph) -> HashMap<String, (i32, i32)> {
+    let mut columns = HashMap::<&str, i32>::new();
+    // at most as many passes as there are nodes, which also stops at cycles
+    for _ in 0..graph.nodes.len() {
+        let mut changed = false;
+        for (dependent, dependency) in &graph.edges {
+            let column = columns
+                .get(dependency.as_str())
+                .copied()
+                .unwrap_or_default()
+                + 1;
+            if columns.get(dependent.as_str()).copied().unwrap_or_default() < column {
+                columns.insert(dependent, column);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut rows = HashMap::<i32, i32>::new();
+    graph
+        .nodes
+        .iter()
+        .map(|node| {
+            let column = columns.get(node.id.as_str()).copied().unwrap_or_default();
+            let row = rows.entry(column).or_default();
+            *row += 1;
+            (node.id.clone(), (column, *row - 1))
+        })
+        .collect()
+}
+
+/// whether notifications about an item are muted, with a callback that toggles it
+fn use_muted(item: NotifyItem) -> (bool, Callback<MouseEvent>) {
+    let muted = use_state(|| false);
+
+    {
+        let muted = muted.clone();
+        use_effect_with_deps(
+            move |item: &NotifyItem| {
+                let item = item.clone();
+                spawn_local(async move {
+                    let resp = ListMuted {}.invoke().await;
+                    muted.set(resp.contains(&item));
+                });
+                || {}
+            },
+            item.clone(),
+        );
+    }
+
+    let toggle = {
+        let muted = muted.clone();
+        Callback::from(move |_| {
+            let item = item.clone();
+            let value = !*muted;
+            muted.set(value);
+            spawn_local(async move { SetMuted { item, muted: value }.invoke().await });
+        })
+    };
+
+    (*muted, toggle)
+}
+
+/// a bell that mutes and unmutes the notifications about an item
+fn mute_button(muted: bool, toggle: Callback<MouseEvent>) -> Html {
+    let title = match muted {
+        true => "Unmute notifications",
+        false => "Mute notifications",
+    };
+    html! {
+        <button class="mute" onclick={toggle} {title}>{if muted {"🔕"} else {"🔔"}}</button>
+    }
+}
+
 #[derive(Properties, PartialEq)]
 pub struct HealthcheckEntryProps {
+    section: String,
     hc: ServiceHealthCheck,
 }
 
+/// How far back the uptime bar and latency sparkline go
+const HISTORY_WINDOW_MS: u64 = 60 * 60 * 1000;
+
+/// How many segments the uptime bar is split into
+const UPTIME_SEGMENTS: u64 = 60;
+
+/// How often to fetch the history, on top of whenever the service changes
+const HISTORY_REFRESH_MS: u32 = 60_000;
+
 #[function_component(HealthcheckEntry)]
 fn healthcheck_entry(props: &HealthcheckEntryProps) -> Html {
     let href = props.hc.url.clone();
+    let history = use_state(Vec::<HealthSample>::new);
+    let (muted, toggle_muted) = use_muted(NotifyItem::Service {
+        section: props.section.clone(),
+        name: props.hc.name.clone(),
+    });
+
+    {
+        let history = history.clone();
+        let section = props.section.clone();
+        let service = props.hc.name.clone();
+        use_effect_with_deps(
+            move |_| {
+                let fetch = move || {
+                    let history = history.clone();
+                    let section = section.clone();
+                    let service = service.clone();
+                    spawn_local(async move {
+                        let since = now_ms().saturating_sub(HISTORY_WINDOW_MS);
+                        let resp = GetHealthHistory {
+                            section,
+                            service,
+                            since,
+                        }
+                        .invoke()
+                        .await;
+                        match resp {
+                            HealthHistoryResponse::Ok(samples) => history.set(samples),
+                            HealthHistoryResponse::Err(e) => {
+                                log(&format!("unable to fetch healthcheck history: {}", e))
+                            }
+                        }
+                    });
+                };
+                fetch();
+                let interval = Interval::new(HISTORY_REFRESH_MS, fetch);
+                move || drop(interval)
+            },
+            props.hc.clone(),
+        );
+    }
+
     html! {
         <div class="podman-card">
             <div class="main" style="display: flex; align-items: center; justify-content: space-between; padding: 0.5em 1em; border: 1px solid #ddd; border-top-left-radius: 0.5em; border-top-right-radius: 0.5em; background-color: white">
                 <div style="display:flex; flex-direction: row; align-items: center; gap: 0.5em">
-                <div style="text-align: left; font-weight: 500;">{&props.hc.name}</div>
-                <div style="display: flex; flex-direction: row; gap: 0.5em">
-                    {match props.hc.db {
-                        Some(db) => html!{<div class="chip" style={if db {"border-color: orange; background-color: #fff3cb; color: orange"} else {""}}>{"Database"}</div>},
-                        None => html!{}
+                <div style="display:flex; flex-direction: column; text-align: left; font-weight: 500;">
+                    {&props.hc.name}
+                    {match &props.hc.build {
+                        Some(build) => html!{
+                            <div style="font-weight: 400; font-size: 0.9em; opacity: 0.3">{build_summary(build)}</div>
+                        },
+                        None => html!{},
+                    }}
+                </div>
+                <div style="display: flex; flex-direction: row; flex-wrap: wrap; gap: 0.5em">
+                    {match props.hc.build.as_ref().and_then(|b| b.expected_commit.as_ref()) {
+                        Some(expected) => html!{
+                            <div class="chip unhealthy" title={format!("expected commit {}", expected)}>{"Unexpected commit"}</div>
+                        },
+                        None => html!{},
                     }}
-                    {match props.hc.elasticsearch {
-                        Some(db) => html!{<div class="chip" style={if db {"border-color: rgb(193, 0, 255); background-color: #eacbff; color: rgb(193, 0, 255)"} else {""}}>{"Elasticsearch"}</div>},
-                        None => html!{}
+                    {match props.hc.build.as_ref().and_then(|b| b.expected_version.as_ref()) {
+                        Some(expected) => html!{
+                            <div class="chip unhealthy" title={format!("expected version {}", expected)}>{"Unexpected version"}</div>
+                        },
+                        None => html!{},
                     }}
+                    {component_chips(&props.hc.components, None)}
                 </div>
             </div>
-            {match props.hc.up {
-                true => html!{<div class="online">{"ONLINE"}</div>},
-                false => html!{<div class="offline">{"OFFLINE"}</div>},
-            }}
+            <div style="display: flex; align-items: center; gap: 0.5em">
+                <div class="latency">
+                    {match props.hc.status {
+                        Some(status) => format!("{} · {} ms", status, props.hc.latency_ms),
+                        None => format!("{} ms", props.hc.latency_ms),
+                    }}
+                </div>
+                {mute_button(muted, toggle_muted)}
+                {match (props.hc.up, &props.hc.blocked_by) {
+                    (true, _) => html!{<div class="online">{"ONLINE"}</div>},
+                    (false, Some(_)) => html!{<div class="pending">{"BLOCKED"}</div>},
+                    (false, None) => html!{<div class="offline">{"OFFLINE"}</div>},
+                }}
+            </div>
         </div>
+        {match history.is_empty() {
+            true => html!{},
+            false => html!{
+                <div class="history">
+                    {uptime_bar(&history, now_ms())}
+                    {sparkline(&history)}
+                </div>
+            },
+        }}
         {match props.hc.up {
             true => html!{
                 <a
@@ -199,46 +622,284 @@ fn healthcheck_entry(props: &HealthcheckEntryProps) -> Html {
                     {&props.hc.url}
                 </a>
             },
-            false => html!{},
+            false => match &props.hc.reason {
+                Some(reason) => html!{<div class="toast">{reason}</div>},
+                None => html!{},
+            },
         }}
     </div>
     }
 }
 
+fn now_ms() -> u64 {
+    js_sys::Date::now() as u64
+}
+
+/// the history window split into segments, each coloured by whether the service was
+/// up, down or both during it
+fn uptime_bar(samples: &[HealthSample], now: u64) -> Html {
+    let start = now.saturating_sub(HISTORY_WINDOW_MS);
+    let width = HISTORY_WINDOW_MS / UPTIME_SEGMENTS;
+    let up = samples.iter().filter(|s| s.up).count();
+    let title = format!(
+        "{:.1}% up over the last hour",
+        100.0 * up as f64 / samples.len() as f64
+    );
+
+    let segments = (0..UPTIME_SEGMENTS)
+        .map(|i| {
+            let from = start + i * width;
+            let segment = samples
+                .iter()
+                .filter(|s| s.timestamp >= from && s.timestamp < from + width);
+            let (up, down) = segment.fold((0, 0), |(up, down), s| match s.up {
+                true => (up + 1, down),
+                false => (up, down + 1),
+            });
+            let class = match (up, down) {
+                (0, 0) => "segment",
+                (_, 0) => "segment up",
+                (0, _) => "segment down",
+                _ => "segment flapping",
+            };
+            html! {<div {class} />}
+        })
+        .collect::<Html>();
+
+    html! {<div class="uptime" {title}>{segments}</div>}
+}
+
+/// the latency of every check in the history, scaled to the slowest one
+fn sparkline(samples: &[HealthSample]) -> Html {
+    let max = samples
+        .iter()
+        .map(|s| s.latency_ms)
+        .max()
+        .unwrap_or_default()
+        .max(1);
+    let step = 100.0 / (samples.len().max(2) - 1) as f64;
+    let points = samples
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            format!(
+                "{:.1},{:.1}",
+                i as f64 * step,
+                20.0 - 20.0 * s.latency_ms as f64 / max as f64
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    html! {
+        <svg class="sparkline" viewBox="0 0 100 20" preserveAspectRatio="none">
+            <title>{format!("up to {} ms", max)}</title>
+            <polyline {points} />
+        </svg>
+    }
+}
+
+/// How many characters of a commit hash to show
+const SHORT_COMMIT: usize = 7;
+
+/// the version, commit and age of a build on a single line
+fn build_summary(build: &BuildInfo) -> String {
+    let age = build
+        .time
+        .as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| {
+            format!(
+                "built {}",
+                HumanTime::from(t.with_timezone(&Local) - Local::now())
+            )
+        });
+    [
+        build.version.clone(),
+        build
+            .commit
+            .as_ref()
+            .map(|c| c.chars().take(SHORT_COMMIT).collect()),
+        age,
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(" · ")
+}
+
+/// a chip for every actuator component, with the components of composites following
+/// their parent and named after it
+fn component_chips(components: &[HealthComponent], parent: Option<&str>) -> Html {
+    components
+        .iter()
+        .map(|c| {
+            let name = match parent {
+                Some(parent) => format!("{} › {}", parent, c.name),
+                None => c.name.clone(),
+            };
+            let details = c
+                .details
+                .iter()
+                .map(|(k, v)| format!("{}: {}", k, v))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let class = match c.status {
+                ComponentStatus::Up => "chip healthy",
+                ComponentStatus::Down => "chip unhealthy",
+                ComponentStatus::OutOfService => "chip starting",
+                ComponentStatus::Unknown => "chip",
+            };
+            html! {
+                <>
+                    <div {class} title={details}>{&name}</div>
+                    {component_chips(&c.components, Some(&name))}
+                </>
+            }
+        })
+        .collect()
+}
+
 #[derive(Properties, PartialEq)]
 pub struct PodmanSectionProps {}
 
+/// The container list, kept in sync with the events pushed by the backend
+struct Containers {
+    list: Option<ListContainerResponse>,
+    /// the events pushed while the list is requested, which may be newer than it and are
+    /// applied on top of it
+    pending: Option<Vec<ContainerEvent>>,
+}
+
+enum ContainersAction {
+    Listed(ListContainerResponse),
+    Event(ContainerEvent),
+}
+
+impl Containers {
+    fn apply(&mut self, event: ContainerEvent) {
+        let mut items = match (&self.list, event) {
+            (_, ContainerEvent::Reset(resp)) => {
+                self.list = Some(resp);
+                return;
+            }
+            (Some(ListContainerResponse::Ok(items)), event) => {
+                let mut items = items.clone();
+                match event {
+                    ContainerEvent::Updated(item) => {
+                        items.retain(|i| i.id != item.id);
+                        items.push(item);
+                    }
+                    ContainerEvent::Removed(id) => items.retain(|i| i.id.as_ref() != Some(&id)),
+                    ContainerEvent::Reset(_) => unreachable!(),
+                };
+                items
+            }
+            // no list to apply the change to yet, wait for the next reset
+            _ => return,
+        };
+        items.sort_by_key(|f| f.state);
+        self.list = Some(ListContainerResponse::Ok(items));
+    }
+}
+
+impl Reducible for Containers {
+    type Action = ContainersAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        let mut next = Containers {
+            list: self.list.clone(),
+            pending: self.pending.clone(),
+        };
+        match action {
+            ContainersAction::Listed(resp) => {
+                next.list = Some(resp);
+                for event in next.pending.take().unwrap_or_default() {
+                    next.apply(event);
+                }
+            }
+            ContainersAction::Event(event) => {
+                if let Some(pending) = &mut next.pending {
+                    pending.push(event.clone());
+                }
+                next.apply(event);
+            }
+        }
+        Rc::new(next)
+    }
+}
+
 #[function_component(PodmanSection)]
 fn podman_section(props: &PodmanSectionProps) -> Html {
-    let greet_msg = use_state(|| Option::None);
+    let containers = use_reducer(|| Containers {
+        list: None,
+        pending: Some(vec![]),
+    });
 
     {
-        let greet_msg = greet_msg.clone();
-        let timeout = Timeout::new(1_000, move || {
+        let containers = containers.clone();
+        use_effect_with_deps(
+            move |_| {
+                let subscription = {
+                    let containers = containers.clone();
+                    ContainerEvent::subscribe(move |e| {
+                        containers.dispatch(ContainersAction::Event(e))
+                    })
+                };
+                spawn_local(async move {
+                    let resp = ListContainers {}.invoke().await;
+                    containers.dispatch(ContainersAction::Listed(resp));
+                });
+                move || drop(subscription)
+            },
+            (),
+        );
+    }
+
+    let error = use_toast();
+
+    let run = |action: PodAction| {
+        let error = error.clone();
+        Callback::from(move |_| {
+            let error = error.clone();
             spawn_local(async move {
-                let resp = ListContainers {}.invoke().await;
-                greet_msg.set(Some(resp));
+                let resp = RunPodAction { action }.invoke().await;
+                if let ActionResponse::Err(e) = resp {
+                    error.show(format!("{:?} pod failed: {}", action, e));
+                }
             });
-        });
-
-        timeout.forget();
-    }
+        })
+    };
 
     html! {
         <Section title="Podman">
-            <div style="display: flex; flex-direction: column; width: 100%; gap: 1em">{match &*greet_msg {
+            <div class="button-row">
+                <button onclick={run(PodAction::Start)}>{"Start all"}</button>
+                <button onclick={run(PodAction::Stop)}>{"Stop all"}</button>
+                <button onclick={run(PodAction::Restart)}>{"Restart all"}</button>
+            </div>
+            {match &*error.message {
+                Some(e) => html!{<div class="toast">{e}</div>},
+                None => html!{},
+            }}
+            <div style="display: flex; flex-direction: column; width: 100%; gap: 1em">{match &containers.list {
             Some(ListContainerResponse::Ok(items)) => html! {
                 { items.iter().cloned().map(|entry| html!{
                     <PodmanEntry
                         id={entry.id}
                         title={entry.name}
                         state={entry.state}
+                        health={entry.health}
                         started_at={entry.started_at}
                         exited_at={entry.exited_at}
-                        link={Option::<String>::None}
+                        ports={entry.ports}
+                        links={entry.links}
+                        image={entry.image}
+                        update_available={entry.update_available}
                     />
                 }).collect::<Html>() }
             },
+            Some(ListContainerResponse::Err(e)) => html! {{e}},
             _ => html! {{"Loading"}}
         }}</div></Section>
     }
@@ -249,39 +910,170 @@ pub struct PodmanEntryProps {
     id: Option<String>,
     title: String,
     state: PodmanState,
+    health: Option<ContainerHealth>,
     started_at: Option<i64>,
     exited_at: Option<i64>,
-    link: Option<String>,
+    ports: Vec<PublishedPort>,
+    links: Vec<String>,
+    image: String,
+    update_available: bool,
+}
+
+/// How long an error toast stays on a card
+const TOAST_DURATION_MS: u32 = 5_000;
+
+/// A message that is shown for a while. A new one replaces the one before along with
+/// its timer, and the timer is dropped with the component.
+#[derive(Clone)]
+struct Toast {
+    message: UseStateHandle<Option<String>>,
+    timer: Rc<RefCell<Option<Timeout>>>,
+}
+
+impl Toast {
+    fn show(&self, text: String) {
+        let message = self.message.clone();
+        message.set(Some(text));
+        let timer = Timeout::new(TOAST_DURATION_MS, move || message.set(None));
+        *self.timer.borrow_mut() = Some(timer);
+    }
+}
+
+fn use_toast() -> Toast {
+    let message = use_state(|| Option::<String>::None);
+    let timer = use_mut_ref(|| Option::<Timeout>::None);
+
+    {
+        let timer = timer.clone();
+        use_effect_with_deps(move |_| move || drop(timer.borrow_mut().take()), ());
+    }
+
+    Toast { message, timer }
 }
 
 #[function_component(PodmanEntry)]
 fn podman_entry(props: &PodmanEntryProps) -> Html {
     let props = Arc::new(props.to_owned());
-    let toggle = {
+    let error = use_toast();
+    let (muted, toggle_muted) = use_muted(NotifyItem::Container(props.title.clone()));
+
+    // the latest progress of an image pull for this container
+    let pull = use_state(|| Option::<String>::None);
+
+    {
+        let pull = pull.clone();
+        use_effect_with_deps(
+            move |id| {
+                let id = id.clone();
+                let subscription = PullEvent::subscribe(move |e| {
+                    if Some(&e.id) == id.as_ref() {
+                        pull.set(Some(e.status));
+                    }
+                });
+                move || drop(subscription)
+            },
+            props.id.clone(),
+        );
+    }
+
+    let run = {
+        let props = props.clone();
+        let error = error.clone();
+        let pull = pull.clone();
+        move |action: ContainerAction| {
+            let props = props.clone();
+            let error = error.clone();
+            let pull = pull.clone();
+            Callback::from(move |_| {
+                let id = match &props.id {
+                    Some(s) => s.clone(),
+                    None => return,
+                };
+                let error = error.clone();
+                let pull = pull.clone();
+                spawn_local(async move {
+                    let resp = RunContainerAction { id, action }.invoke().await;
+                    pull.set(None);
+                    if let ActionResponse::Err(e) = resp {
+                        error.show(format!("{:?} failed: {}", action, e));
+                    }
+                });
+            })
+        }
+    };
+
+    let terminal = use_state(|| Option::<u32>::None);
+
+    let open_terminal = {
         let props = props.clone();
+        let terminal = terminal.clone();
+        let error = error.clone();
         Callback::from(move |_| {
             let id = match &props.id {
                 Some(s) => s.clone(),
                 None => return,
             };
-            let desired_state = match &props.state {
-                PodmanState::Running => PodmanState::Stopped,
-                _ => PodmanState::Running,
-            };
+            let terminal = terminal.clone();
+            let error = error.clone();
             spawn_local(async move {
-                SetContainerStatus {
+                let resp = OpenTerminal {
                     id,
-                    state: desired_state,
+                    command: None,
+                    rows: TERMINAL_ROWS,
+                    cols: TERMINAL_COLS,
                 }
                 .invoke()
                 .await;
+                match resp {
+                    OpenTerminalResponse::Ok(session) => terminal.set(Some(session)),
+                    OpenTerminalResponse::Err(e) => {
+                        error.show(format!("Shell failed: {}", e));
+                    }
+                }
             });
         })
     };
 
+    let close_terminal = {
+        let terminal = terminal.clone();
+        Callback::from(move |_| terminal.set(None))
+    };
+
+    let toggle = run(match &props.state {
+        PodmanState::Running => ContainerAction::Stop,
+        PodmanState::Paused => ContainerAction::Unpause,
+        _ => ContainerAction::Start,
+    });
+
+    let mut actions = match props.update_available {
+        true => vec![ContainerAction::Update],
+        false => vec![],
+    };
+    actions.extend_from_slice(match props.state {
+        PodmanState::Running => &[
+            ContainerAction::Restart,
+            ContainerAction::Pause,
+            ContainerAction::Kill,
+            ContainerAction::Recreate,
+            ContainerAction::Remove,
+        ],
+        PodmanState::Paused => &[
+            ContainerAction::Unpause,
+            ContainerAction::Kill,
+            ContainerAction::Recreate,
+            ContainerAction::Remove,
+        ],
+        PodmanState::Restarting | PodmanState::Stopping => &[
+            ContainerAction::Kill,
+            ContainerAction::Recreate,
+            ContainerAction::Remove,
+        ],
+        _ => &[ContainerAction::Recreate, ContainerAction::Remove],
+    });
+
     let local = Local::now().timestamp();
     let start = match &props.state {
-        PodmanState::Running => props.started_at.unwrap_or(0),
+        PodmanState::Running | PodmanState::Paused => props.started_at.unwrap_or(0),
         _ => props.exited_at.unwrap_or(0),
     };
 
@@ -292,34 +1084,579 @@ fn podman_entry(props: &PodmanEntryProps) -> Html {
         <div class="podman-card">
             <div style="display: flex; align-items: center; justify-content: space-between; padding: 0.5em 1em; border: 1px solid #ddd; border-top-left-radius: 0.5em; border-top-right-radius: 0.5em; background-color: white">
                 <div style="display:flex; flex-direction: column; font-weight: 500;">
-                    <div>{&props.title}</div>
-                    <div style="text-align: left; font-weight: 400; font-size: 0.9em; opacity: 0.3">{human}</div>
+                    <div style="display: flex; flex-direction: row; align-items: center; gap: 0.5em">
+                        {&props.title}
+                        {match props.health {
+                            Some(ContainerHealth::Healthy) => html!{<div class="chip healthy">{"Healthy"}</div>},
+                            Some(ContainerHealth::Unhealthy) => html!{<div class="chip unhealthy">{"Unhealthy"}</div>},
+                            Some(ContainerHealth::Starting) => html!{<div class="chip starting">{"Starting"}</div>},
+                            None => html!{},
+                        }}
+                        {match props.update_available {
+                            true => html!{<div class="chip update" title={props.image.clone()}>{"Update available"}</div>},
+                            false => html!{},
+                        }}
+                    </div>
+                    <div style="text-align: left; font-weight: 400; font-size: 0.9em; opacity: 0.3">
+                        {human}
+                        {props.ports.iter().map(|p| format!(" · {}→{}/{}", p.host_port, p.container_port, p.protocol)).collect::<String>()}
+                    </div>
                 </div>
-                <button onclick={toggle} class={if props.state == PodmanState::Running {"online"} else {"offline"}}>
-                    {match props.state {
-                        PodmanState::Running => "RUNNING",
-                        PodmanState::Exited => "EXITED",
-                        PodmanState::Stopped=> "STOPPED",
-                        PodmanState::Stopping=> "STOPPING",
-                    }}
-                </button>
+                <div style="display: flex; align-items: center; gap: 0.5em">
+                    <button onclick={toggle} class={match props.state {
+                        PodmanState::Running => "online",
+                        PodmanState::Restarting
+                        | PodmanState::Paused
+                        | PodmanState::Stopping
+                        | PodmanState::Created
+                        | PodmanState::Configured
+                        | PodmanState::Initialized => "pending",
+                        _ => "offline",
+                    }}>
+                        {match props.state {
+                            PodmanState::Running => "RUNNING",
+                            PodmanState::Restarting => "RESTARTING",
+                            PodmanState::Paused => "PAUSED",
+                            PodmanState::Stopping => "STOPPING",
+                            PodmanState::Stopped => "STOPPED",
+                            PodmanState::Exited => "EXITED",
+                            PodmanState::Created => "CREATED",
+                            PodmanState::Configured => "CONFIGURED",
+                            PodmanState::Initialized => "INITIALIZED",
+                            PodmanState::Removing => "REMOVING",
+                            PodmanState::Dead => "DEAD",
+                            PodmanState::Unknown => "UNKNOWN",
+                        }}
+                    </button>
+                    <details class="actions">
+                        <summary>{"⋯"}</summary>
+                        <div class="menu">
+                            {match props.state {
+                                PodmanState::Running => html!{<button onclick={open_terminal}>{"Shell"}</button>},
+                                _ => html!{},
+                            }}
+                            {actions.iter().map(|a| html!{
+                                <button onclick={run(*a)}>{format!("{:?}", a)}</button>
+                            }).collect::<Html>()}
+                            <button onclick={toggle_muted}>{if muted {"Unmute"} else {"Mute"}}</button>
+                        </div>
+                    </details>
+                </div>
+            </div>
+            {props.links.iter().map(|link| html!{
+                <a
+                    href={link.clone()}
+                    target="_blank"
+                    style={if props.state == PodmanState::Running
+                        {"background-color: #d6ffd6; color: rgb(55, 213, 34); border: 1px solid rgb(175, 254, 137)"}
+                        else {""}
+                    }
+                >
+                    {link}
+                </a>
+            }).collect::<Html>()}
+            {match &*pull {
+                Some(status) => html!{<div class="progress">{status}</div>},
+                None => html!{},
+            }}
+            {match *terminal {
+                Some(session) => html!{<Terminal {session} onclose={close_terminal} />},
+                None => html!{},
+            }}
+            {match &*error.message {
+                Some(e) => html!{<div class="toast">{e}</div>},
+                None => html!{},
+            }}
+        </div>
+    }
+}
+
+/// How many lines of progress to keep per stack
+const STACK_LOG_LINES: usize = 50;
+
+/// The progress reported by the backend, per stack
+#[derive(Default, PartialEq)]
+struct StackLogs(HashMap<String, Vec<String>>);
+
+impl Reducible for StackLogs {
+    type Action = StackEvent;
+
+    fn reduce(self: Rc<Self>, event: StackEvent) -> Rc<Self> {
+        let mut logs = self.0.clone();
+        let lines = logs.entry(event.stack).or_default();
+        lines.push(event.line);
+        if lines.len() > STACK_LOG_LINES {
+            lines.drain(..lines.len() - STACK_LOG_LINES);
+        }
+        Rc::new(StackLogs(logs))
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct StacksSectionProps {}
+
+#[function_component(StacksSection)]
+fn stacks_section(_props: &StacksSectionProps) -> Html {
+    let stacks = use_state(|| Option::<Vec<StackItem>>::None);
+    let logs = use_reducer(StackLogs::default);
+
+    {
+        let stacks = stacks.clone();
+        let logs = logs.clone();
+        use_effect_with_deps(
+            move |_| {
+                let subscription = StackEvent::subscribe(move |e| logs.dispatch(e));
+                spawn_local(async move {
+                    stacks.set(Some(ListStacks {}.invoke().await));
+                });
+                move || drop(subscription)
+            },
+            (),
+        );
+    }
+
+    match &*stacks {
+        Some(stacks) if !stacks.is_empty() => html! {
+            <Section title="Stacks">
+                <div style="display: flex; flex-direction: column; width: 100%; gap: 1em">
+                    { stacks.iter().cloned().map(|stack| html!{
+                        <StackEntry
+                            log={logs.0.get(&stack.name).cloned().unwrap_or_default()}
+                            {stack}
+                        />
+                    }).collect::<Html>() }
+                </div>
+            </Section>
+        },
+        _ => html! {},
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct StackEntryProps {
+    stack: StackItem,
+    log: Vec<String>,
+}
+
+#[function_component(StackEntry)]
+fn stack_entry(props: &StackEntryProps) -> Html {
+    let running = use_state(|| Option::<StackAction>::None);
+    let error = use_toast();
+
+    let run = |action: StackAction| {
+        let name = props.stack.name.clone();
+        let running = running.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            let name = name.clone();
+            let running = running.clone();
+            let error = error.clone();
+            running.set(Some(action));
+            spawn_local(async move {
+                let resp = RunStackAction { name, action }.invoke().await;
+                running.set(None);
+                if let ActionResponse::Err(e) = resp {
+                    error.show(format!("{:?} failed: {}", action, e));
+                }
+            });
+        })
+    };
+
+    html! {
+        <div class="podman-card">
+            <div style="display: flex; align-items: center; justify-content: space-between; padding: 0.5em 1em; border: 1px solid #ddd; border-top-left-radius: 0.5em; border-top-right-radius: 0.5em; background-color: white">
+                <div style="display:flex; flex-direction: column; font-weight: 500;">
+                    {&props.stack.name}
+                    <div style="text-align: left; font-weight: 400; font-size: 0.9em; opacity: 0.3">{&props.stack.file}</div>
+                </div>
+                {match *running {
+                    Some(StackAction::Up) => html!{<div class="pending">{"STARTING"}</div>},
+                    Some(StackAction::Down) => html!{<div class="pending">{"STOPPING"}</div>},
+                    None => html!{
+                        <div class="button-row">
+                            <button onclick={run(StackAction::Up)}>{"Up"}</button>
+                            <button onclick={run(StackAction::Down)}>{"Down"}</button>
+                        </div>
+                    },
+                }}
             </div>
-            {match props.link.clone() {
-                Some(link) => html!{
-                    <a
-                        href={link.clone()}
-                        target="_blank"
-                        style={if props.state == PodmanState::Running
-                            {"background-color: #d6ffd6; color: rgb(55, 213, 34); border: 1px solid rgb(175, 254, 137)"}
-                            else {""}
+            {match props.log.is_empty() {
+                true => html!{},
+                false => html!{<pre class="stack-log">{props.log.join("\n")}</pre>},
+            }}
+            {match &*error.message {
+                Some(e) => html!{<div class="toast">{e}</div>},
+                None => html!{},
+            }}
+        </div>
+    }
+}
+
+/// formats a number of bytes the way `podman system df` does
+fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "kB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} {}", bytes, UNITS[0]),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct StorageSectionProps {}
+
+#[function_component(StorageSection)]
+fn storage_section(_props: &StorageSectionProps) -> Html {
+    let usage = use_state(|| Option::<DiskUsageResponse>::None);
+    // bumped after every prune to refresh the usage
+    let refresh = use_state(|| 0u32);
+
+    {
+        let usage = usage.clone();
+        use_effect_with_deps(
+            move |_| {
+                spawn_local(async move {
+                    usage.set(Some(GetDiskUsage {}.invoke().await));
+                });
+                || {}
+            },
+            *refresh,
+        );
+    }
+
+    let onpruned = {
+        let refresh = refresh.clone();
+        Callback::from(move |_| refresh.set(*refresh + 1))
+    };
+
+    html! {
+        <Section title="Storage">
+            {match &*usage {
+                Some(DiskUsageResponse::Ok(DiskUsage { images, containers, volumes, build_cache })) => html! {
+                    <table class="storage">
+                        <tr><th></th><th>{"Count"}</th><th>{"Size"}</th><th>{"Reclaimable"}</th><th></th></tr>
+                        <StorageRow title="Images" usage={images.clone()} />
+                        <StorageRow title="Build cache" usage={build_cache.clone()} target={PruneTarget::DanglingImages} onpruned={onpruned.clone()} />
+                        <StorageRow title="Containers" usage={containers.clone()} target={PruneTarget::StoppedContainers} onpruned={onpruned.clone()} />
+                        <StorageRow title="Volumes" usage={volumes.clone()} target={PruneTarget::UnusedVolumes} {onpruned} />
+                    </table>
+                },
+                Some(DiskUsageResponse::Err(e)) => html! {{e}},
+                None => html! {{"Loading"}},
+            }}
+        </Section>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct StorageRowProps {
+    title: String,
+    usage: DiskUsageSummary,
+    /// what pruning this row cleans up, if it can be pruned
+    #[prop_or_default]
+    target: Option<PruneTarget>,
+    #[prop_or_default]
+    onpruned: Callback<()>,
+}
+
+#[function_component(StorageRow)]
+fn storage_row(props: &StorageRowProps) -> Html {
+    let preview = use_state(|| Option::<Vec<PruneCandidate>>::None);
+    let message = use_toast();
+
+    let show_message = {
+        let message = message.clone();
+        move |text: String| message.show(text)
+    };
+
+    let open_preview = {
+        let preview = preview.clone();
+        let show_message = show_message.clone();
+        props.target.map(|target| {
+            Callback::from(move |_| {
+                let preview = preview.clone();
+                let show_message = show_message.clone();
+                spawn_local(async move {
+                    let resp = PreviewPrune { target }.invoke().await;
+                    match resp {
+                        PreviewPruneResponse::Ok(candidates) => preview.set(Some(candidates)),
+                        PreviewPruneResponse::Err(e) => {
+                            show_message(format!("Preview failed: {}", e))
                         }
-                    >
-                        {&link}
-                    </a>
+                    }
+                });
+            })
+        })
+    };
+
+    let cancel = {
+        let preview = preview.clone();
+        Callback::from(move |_| preview.set(None))
+    };
+
+    let confirm = {
+        let preview = preview.clone();
+        let onpruned = props.onpruned.clone();
+        props.target.map(|target| {
+            Callback::from(move |_| {
+                let preview = preview.clone();
+                let onpruned = onpruned.clone();
+                let show_message = show_message.clone();
+                spawn_local(async move {
+                    let resp = Prune { target }.invoke().await;
+                    preview.set(None);
+                    onpruned.emit(());
+                    match resp {
+                        PruneResponse::Ok { removed, reclaimed } => show_message(format!(
+                            "Removed {} items, freeing {}",
+                            removed,
+                            human_size(reclaimed)
+                        )),
+                        PruneResponse::Err(e) => show_message(format!("Prune failed: {}", e)),
+                    }
+                });
+            })
+        })
+    };
+
+    html! {
+        <>
+            <tr>
+                <td>{&props.title}</td>
+                <td>{props.usage.count}</td>
+                <td>{human_size(props.usage.size)}</td>
+                <td>{human_size(props.usage.reclaimable)}</td>
+                <td>{match open_preview {
+                    Some(onclick) => html!{<button {onclick}>{"Prune"}</button>},
+                    None => html!{},
+                }}</td>
+            </tr>
+            {match (&*preview, confirm) {
+                (Some(candidates), Some(confirm)) => html! {
+                    <tr><td colspan="5" class="prune-preview">
+                        {match candidates.is_empty() {
+                            true => html!{<div>{"Nothing to remove"}</div>},
+                            false => html!{
+                                <ul>
+                                    {candidates.iter().map(|c| html!{
+                                        <li title={c.id.clone()}>{format!("{} ({})", c.name, human_size(c.size))}</li>
+                                    }).collect::<Html>()}
+                                </ul>
+                            },
+                        }}
+                        <div class="button-row">
+                            <button onclick={confirm} disabled={candidates.is_empty()}>
+                                {format!("Remove {} items, freeing {}", candidates.len(), human_size(candidates.iter().map(|c| c.size).sum()))}
+                            </button>
+                            <button onclick={cancel}>{"Cancel"}</button>
+                        </div>
+                    </td></tr>
                 },
                 _ => html!{},
             }}
-        </div>
+            {match &*message.message {
+                Some(m) => html!{<tr><td colspan="5" class="toast">{m}</td></tr>},
+                None => html!{},
+            }}
+        </>
+    }
+}
+
+/// The size the embedded terminals are opened with, until they are fitted to the page
+const TERMINAL_ROWS: u16 = 24;
+const TERMINAL_COLS: u16 = 80;
+
+/// How much terminal output to keep around
+const TERMINAL_SCROLLBACK: usize = 64 * 1024;
+
+#[derive(Properties, PartialEq)]
+pub struct TerminalProps {
+    session: u32,
+    onclose: Callback<()>,
+}
+
+/// The text shown in a terminal, with control sequences already applied
+#[derive(Default, PartialEq)]
+struct TerminalOutput(String);
+
+impl Reducible for TerminalOutput {
+    type Action = String;
+
+    fn reduce(self: Rc<Self>, data: String) -> Rc<Self> {
+        let mut text = self.0.clone();
+        let mut chars = data.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                // escape sequences are skipped, we only render plain text
+                '\x1b' => match chars.next() {
+                    Some('[') => {
+                        for c in chars.by_ref() {
+                            if ('@'..='~').contains(&c) {
+                                break;
+                            }
+                        }
+                    }
+                    Some(']') => {
+                        while let Some(c) = chars.next() {
+                            if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                                break;
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                '\x08' => {
+                    text.pop();
+                }
+                '\r' | '\x07' => {}
+                c => text.push(c),
+            }
+        }
+
+        if text.len() > TERMINAL_SCROLLBACK {
+            let mut start = text.len() - TERMINAL_SCROLLBACK;
+            while !text.is_char_boundary(start) {
+                start += 1;
+            }
+            text.drain(..start);
+        }
+
+        Rc::new(TerminalOutput(text))
+    }
+}
+
+/// How many rows and columns of text fit in the terminal, measured by a single cell.
+fn terminal_size(terminal: &NodeRef, cell: &NodeRef) -> Option<(u16, u16)> {
+    let terminal = terminal.cast::<web_sys::HtmlElement>()?;
+    let cell = cell.cast::<web_sys::HtmlElement>()?;
+    let (width, height) = (cell.offset_width(), cell.offset_height());
+    if width == 0 || height == 0 {
+        return None;
+    }
+    // less the padding, which is a cell high and about two wide on each side
+    let rows = (terminal.client_height() / height - 1).max(1);
+    let cols = (terminal.client_width() / width - 4).max(1);
+    Some((rows as u16, cols as u16))
+}
+
+/// Fits the pty of a session to the size of its terminal.
+fn fit_terminal(terminal: &NodeRef, cell: &NodeRef, session: u32) {
+    if let Some((rows, cols)) = terminal_size(terminal, cell) {
+        spawn_local(async move {
+            ResizeTerminal {
+                session,
+                rows,
+                cols,
+            }
+            .invoke()
+            .await
+        });
+    }
+}
+
+/// translates a key press into the bytes a terminal would send
+fn key_input(e: &KeyboardEvent) -> Option<String> {
+    let key = e.key();
+    if e.ctrl_key() && key.len() == 1 {
+        // control characters are the letter's offset from `@`
+        let c = key.to_ascii_uppercase().as_bytes()[0];
+        return (b'@'..=b'_')
+            .contains(&c)
+            .then(|| ((c - b'@') as char).to_string());
+    }
+
+    Some(
+        match key.as_str() {
+            "Enter" => "\r",
+            "Backspace" => "\x7f",
+            "Tab" => "\t",
+            "Escape" => "\x1b",
+            "ArrowUp" => "\x1b[A",
+            "ArrowDown" => "\x1b[B",
+            "ArrowRight" => "\x1b[C",
+            "ArrowLeft" => "\x1b[D",
+            k if k.chars().count() == 1 => k,
+            _ => return None,
+        }
+        .to_string(),
+    )
+}
+
+#[function_component(Terminal)]
+fn terminal(props: &TerminalProps) -> Html {
+    let output = use_reducer(TerminalOutput::default);
+    let node = use_node_ref();
+    let cell = use_node_ref();
+
+    {
+        let node = node.clone();
+        let cell = cell.clone();
+        let session = props.session;
+        use_effect_with_deps(
+            move |_| {
+                fit_terminal(&node, &cell, session);
+                let onresize = Closure::wrap(
+                    Box::new(move || fit_terminal(&node, &cell, session)) as Box<dyn Fn()>
+                );
+                let window = web_sys::window().expect("running in a browser");
+                window
+                    .add_event_listener_with_callback("resize", onresize.as_ref().unchecked_ref())
+                    .ok();
+                move || {
+                    window
+                        .remove_event_listener_with_callback(
+                            "resize",
+                            onresize.as_ref().unchecked_ref(),
+                        )
+                        .ok();
+                }
+            },
+            session,
+        );
+    }
+
+    {
+        let output = output.clone();
+        let session = props.session;
+        let onclose = props.onclose.clone();
+        use_effect_with_deps(
+            move |_| {
+                let subscription = TerminalEvent::subscribe(move |e| match e {
+                    TerminalEvent::Output { session: s, data } if s == session => {
+                        output.dispatch(data)
+                    }
+                    TerminalEvent::Exited { session: s } if s == session => onclose.emit(()),
+                    _ => {}
+                });
+                move || {
+                    drop(subscription);
+                    spawn_local(async move { CloseTerminal { session }.invoke().await });
+                }
+            },
+            session,
+        );
+    }
+
+    let onkeydown = {
+        let session = props.session;
+        Callback::from(move |e: KeyboardEvent| {
+            if let Some(data) = key_input(&e) {
+                e.prevent_default();
+                spawn_local(async move { WriteTerminal { session, data }.invoke().await });
+            }
+        })
+    };
+
+    html! {
+        <pre class="terminal" tabindex="0" ref={node} {onkeydown}>
+            <span class="terminal-cell" ref={cell}>{"M"}</span>
+            {&output.0}
+        </pre>
     }
 }
 
@@ -369,6 +1706,7 @@ pub struct SSHToggleProps {
 #[function_component(SSHToggle)]
 fn ssh_toggle(props: &SSHToggleProps) -> Html {
     let props = Arc::new(props.to_owned());
+    let (muted, toggle_muted) = use_muted(NotifyItem::Tunnel(props.name.clone()));
     let toggle = {
         let props = props.clone();
         Callback::from(move |_| {
@@ -384,12 +1722,15 @@ fn ssh_toggle(props: &SSHToggleProps) -> Html {
     };
 
     html! {
-        <button onclick={toggle} style="flex: 1; display: flex; justify-content: space-between; align-items: center; gap: 2em" class={if props.status == TunnelStatus::Disconnected {""} else {"connected"}}>
-            {&props.name}
-            <span>{match props.status {
-                TunnelStatus::Connected(i) => i.to_string(),
-                TunnelStatus::Disconnected => "❌".to_string()
-            }}</span>
-        </button>
+        <div style="flex: 1; display: flex; align-items: center; gap: 0.5em">
+            <button onclick={toggle} style="flex: 1; display: flex; justify-content: space-between; align-items: center; gap: 2em" class={if props.status == TunnelStatus::Disconnected {""} else {"connected"}}>
+                {&props.name}
+                <span>{match props.status {
+                    TunnelStatus::Connected(i) => i.to_string(),
+                    TunnelStatus::Disconnected => "❌".to_string()
+                }}</span>
+            </button>
+            {mute_button(muted, toggle_muted)}
+        </div>
     }
 }